@@ -17,17 +17,29 @@ extern crate rspack_binding_builder;
 // The second argument to `register_plugin` is a resolver function that is called with `napi::Env` and the options returned from the resolver function from JS side.
 //
 // The resolver function should return a `BoxPlugin` instance.
+//
+// Options passed to `new CompilationLoaderPlugin(options)` are used as the default
+// options of every `builtin:compilation-loader` rule.
 register_plugin!(
   "CompilationLoaderPlugin",
-  |_env: Env, _options: Unknown<'_>| {
-    Ok(Box::new(loader_compilation::CompilationLoaderPlugin::new()) as BoxPlugin)
+  |env: Env, options: Unknown<'_>| {
+    let default_options = env.from_js_value::<Option<serde_json::Value>, _>(options)?;
+    let plugin = loader_compilation::CompilationLoaderPlugin::new(default_options)
+      .map_err(|e| napi::Error::from_reason(format!("Invalid CompilationLoaderPlugin options: {e}")))?;
+    Ok(Box::new(plugin) as BoxPlugin)
   }
 );
 
 // Export the ManifestPlugin
+//
+// Options are deserialized into `ManifestPluginOptions`, falling back to defaults
+// when `new ManifestPlugin()` is called without options.
 register_plugin!(
-  "ManifestPlugin", 
-  |_env: Env, _options: Unknown<'_>| {
-    Ok(Box::new(plugin_manifest::ManifestPlugin::new()) as BoxPlugin)
+  "ManifestPlugin",
+  |env: Env, options: Unknown<'_>| {
+    let options = env
+      .from_js_value::<Option<plugin_manifest::ManifestPluginOptions>, _>(options)?
+      .unwrap_or_default();
+    Ok(Box::new(plugin_manifest::ManifestPlugin::new(options)) as BoxPlugin)
   }
 );
@@ -0,0 +1,590 @@
+use std::{
+  collections::{BTreeSet, HashMap, HashSet},
+  path::{Component, Path, PathBuf},
+};
+
+use rspack_cacheable::{cacheable, cacheable_dyn};
+use rspack_collections::Identifier;
+use rspack_core::{Loader, LoaderContext, RunnerContext};
+use rspack_error::Result;
+use swc_core::{
+  common::{FileName, SourceMap},
+  ecma::{
+    ast::*,
+    atoms::Atom,
+    parser::{lexer::Lexer, EsSyntax, Parser, StringInput, Syntax, TsSyntax},
+  },
+};
+
+pub const BARREL_OPTIMIZE_LOADER_IDENTIFIER: &str = "builtin:barrel-optimize-loader";
+
+/// Handles the `__barrel_optimize__?names=a,b!=!pkg?a,b` requests emitted by
+/// `transformFeatures.namedImportTransform`.
+///
+/// The barrel file is replaced with a module re-exporting only the requested names,
+/// straight from the files declaring them, so the rest of the barrel is never built.
+#[cacheable]
+#[derive(Debug)]
+pub struct BarrelOptimizeLoader {
+  identifier: Identifier,
+}
+
+impl Default for BarrelOptimizeLoader {
+  fn default() -> Self {
+    Self {
+      identifier: BARREL_OPTIMIZE_LOADER_IDENTIFIER.into(),
+    }
+  }
+}
+
+impl BarrelOptimizeLoader {
+  /// Panics:
+  /// Panics if `identifier` passed in is not starting with `builtin:barrel-optimize-loader`.
+  pub fn with_identifier(mut self, identifier: Identifier) -> Self {
+    assert!(identifier.starts_with(BARREL_OPTIMIZE_LOADER_IDENTIFIER));
+    self.identifier = identifier;
+    self
+  }
+
+  fn loader_impl(&self, loader_context: &mut LoaderContext<RunnerContext>) -> Result<()> {
+    let Some(resource_path) = loader_context.resource_path().map(|p| p.to_path_buf()) else {
+      return Ok(());
+    };
+    let Some(content) = loader_context.take_content() else {
+      return Ok(());
+    };
+    let source = content.into_string_lossy();
+    let names = parse_names(loader_context.resource_query().unwrap_or_default());
+    if names.is_empty() {
+      loader_context.finish_with((source, None));
+      return Ok(());
+    }
+
+    let mut resolver = BarrelResolver::default();
+    let code = resolver.optimize(resource_path.as_std_path(), &source, &names);
+    // Rebuild when any file the re-exports were traced through changes
+    loader_context
+      .file_dependencies
+      .extend(resolver.visited_files());
+    loader_context.finish_with((code, None));
+    Ok(())
+  }
+}
+
+#[cacheable_dyn]
+#[async_trait::async_trait]
+impl Loader<RunnerContext> for BarrelOptimizeLoader {
+  fn identifier(&self) -> Identifier {
+    self.identifier
+  }
+
+  #[tracing::instrument("loader:builtin-barrel-optimize", skip_all, fields(
+    perfetto.track_name = "loader:builtin-barrel-optimize",
+    perfetto.process_name = "Loader Analysis",
+    resource = loader_context.resource(),
+  ))]
+  async fn run(&self, loader_context: &mut LoaderContext<RunnerContext>) -> Result<()> {
+    self.loader_impl(loader_context)
+  }
+}
+
+// `?Button,Input` as written by `named_import_transform`
+fn parse_names(query: &str) -> BTreeSet<String> {
+  query
+    .trim_start_matches('?')
+    .split(',')
+    .map(str::trim)
+    .filter(|name| !name.is_empty())
+    .map(str::to_string)
+    .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ExportTarget {
+  // Declared in the module itself
+  Local,
+  // Re-exported from `source`, `imported` is `*` for namespace re-exports
+  Reexport { source: String, imported: String },
+}
+
+#[derive(Debug, Default)]
+struct ModuleExports {
+  named: HashMap<String, ExportTarget>,
+  // Sources of `export * from`
+  wildcards: Vec<String>,
+}
+
+// Where a requested name is finally declared: the module request and its export name
+type ResolvedExport = (String, String);
+
+#[derive(Default)]
+struct BarrelResolver {
+  // Exports of every file read so far, `None` when it could not be parsed
+  modules: HashMap<PathBuf, Option<ModuleExports>>,
+}
+
+impl BarrelResolver {
+  fn optimize(&mut self, barrel_path: &Path, source: &str, names: &BTreeSet<String>) -> String {
+    let barrel_exports = parse_exports(barrel_path, source);
+    self
+      .modules
+      .insert(barrel_path.to_path_buf(), barrel_exports);
+    let barrel_request = path_request(barrel_path);
+
+    names
+      .iter()
+      .map(|name| {
+        let mut visited = HashSet::new();
+        // Fall back to the barrel itself when the declaration cannot be traced
+        let (request, imported) = self
+          .resolve(barrel_path, name, &mut visited)
+          .unwrap_or_else(|| (barrel_request.clone(), name.clone()));
+        create_reexport(name, &request, &imported)
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+
+  fn visited_files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+    self.modules.keys().cloned()
+  }
+
+  fn exports(&mut self, path: &Path) -> Option<&ModuleExports> {
+    if !self.modules.contains_key(path) {
+      let exports = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|source| parse_exports(path, &source));
+      self.modules.insert(path.to_path_buf(), exports);
+    }
+    self.modules.get(path).and_then(Option::as_ref)
+  }
+
+  fn resolve(
+    &mut self,
+    path: &Path,
+    name: &str,
+    visited: &mut HashSet<PathBuf>,
+  ) -> Option<ResolvedExport> {
+    if !visited.insert(path.to_path_buf()) {
+      return None;
+    }
+    let exports = self.exports(path)?;
+    let target = exports.named.get(name).cloned();
+    let wildcards = exports.wildcards.clone();
+
+    match target {
+      Some(ExportTarget::Local) => Some((path_request(path), name.to_string())),
+      Some(ExportTarget::Reexport { source, imported }) => {
+        let Some(file) = resolve_file(path, &source) else {
+          return Some((source_request(path, &source), imported));
+        };
+        if imported == "*" {
+          return Some((path_request(&file), imported));
+        }
+        // Keep following `export { x } from` chains, stopping where the trail goes cold
+        self
+          .resolve(&file, &imported, visited)
+          .or_else(|| Some((path_request(&file), imported)))
+      }
+      // `export * from` never re-exports `default`
+      None if name == "default" => None,
+      None => {
+        for wildcard in wildcards {
+          // Names behind a wildcard that cannot be followed are ambiguous
+          let file = resolve_file(path, &wildcard)?;
+          if let Some(resolved) = self.resolve(&file, name, visited) {
+            return Some(resolved);
+          }
+        }
+        None
+      }
+    }
+  }
+}
+
+fn create_reexport(name: &str, request: &str, imported: &str) -> String {
+  let request = serde_json::to_string(request).expect("strings are always serializable");
+  if imported == "*" {
+    format!("export * as {name} from {request};")
+  } else if imported == name {
+    format!("export {{ {name} }} from {request};")
+  } else {
+    format!("export {{ {imported} as {name} }} from {request};")
+  }
+}
+
+fn path_request(path: &Path) -> String {
+  path.to_string_lossy().into_owned()
+}
+
+// Relative sources become absolute so the re-export no longer depends on the barrel location
+fn source_request(importer: &Path, source: &str) -> String {
+  match importer.parent() {
+    Some(dir) if source.starts_with('.') => path_request(&normalize_path(&dir.join(source))),
+    _ => source.to_string(),
+  }
+}
+
+// Drops `.` and `..` segments without touching the file system
+fn normalize_path(path: &Path) -> PathBuf {
+  let mut normalized = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir => {
+        normalized.pop();
+      }
+      component => normalized.push(component),
+    }
+  }
+  normalized
+}
+
+const RESOLVE_EXTENSIONS: [&str; 7] = ["js", "mjs", "cjs", "jsx", "ts", "tsx", "mts"];
+
+// A minimal file resolution for relative sources, packages are left to Rspack
+fn resolve_file(importer: &Path, source: &str) -> Option<PathBuf> {
+  if !source.starts_with('.') {
+    return None;
+  }
+  let base = normalize_path(&importer.parent()?.join(source));
+  if base.is_file() {
+    return Some(base);
+  }
+  RESOLVE_EXTENSIONS
+    .iter()
+    .map(|ext| PathBuf::from(format!("{}.{ext}", base.display())))
+    .chain(
+      RESOLVE_EXTENSIONS
+        .iter()
+        .map(|ext| base.join(format!("index.{ext}"))),
+    )
+    .find(|candidate| candidate.is_file())
+}
+
+fn parse_exports(path: &Path, source: &str) -> Option<ModuleExports> {
+  let syntax = match path.extension().and_then(|ext| ext.to_str()) {
+    Some("ts" | "mts" | "cts") => Syntax::Typescript(TsSyntax::default()),
+    Some("tsx") => Syntax::Typescript(TsSyntax {
+      tsx: true,
+      ..Default::default()
+    }),
+    _ => Syntax::Es(EsSyntax {
+      jsx: true,
+      ..Default::default()
+    }),
+  };
+  let cm = SourceMap::default();
+  let fm = cm.new_source_file(
+    FileName::Real(path.to_path_buf()).into(),
+    source.to_string(),
+  );
+  let lexer = Lexer::new(syntax, EsVersion::EsNext, StringInput::from(&*fm), None);
+  let module = Parser::new_from(lexer).parse_module().ok()?;
+  Some(collect_exports(&module))
+}
+
+fn collect_exports(module: &Module) -> ModuleExports {
+  let mut exports = ModuleExports::default();
+  // Import bindings, which `export { x }` may re-export
+  let mut imports: HashMap<Atom, ExportTarget> = HashMap::new();
+
+  for item in &module.body {
+    let ModuleItem::ModuleDecl(decl) = item else {
+      continue;
+    };
+    match decl {
+      ModuleDecl::Import(import_decl) if !import_decl.type_only => {
+        let source = import_decl.src.value.to_string();
+        for specifier in &import_decl.specifiers {
+          let (local, imported) = match specifier {
+            ImportSpecifier::Named(named) => (
+              &named.local,
+              named
+                .imported
+                .as_ref()
+                .map_or(named.local.sym.to_string(), |imported| {
+                  imported.atom().to_string()
+                }),
+            ),
+            ImportSpecifier::Default(default) => (&default.local, "default".to_string()),
+            ImportSpecifier::Namespace(namespace) => (&namespace.local, "*".to_string()),
+          };
+          imports.insert(
+            local.sym.clone(),
+            ExportTarget::Reexport {
+              source: source.clone(),
+              imported,
+            },
+          );
+        }
+      }
+      ModuleDecl::ExportNamed(named_export) if !named_export.type_only => {
+        for specifier in &named_export.specifiers {
+          let (exported, target) = match specifier {
+            ExportSpecifier::Named(named) if !named.is_type_only => {
+              let orig = named.orig.atom();
+              let exported = named.exported.as_ref().unwrap_or(&named.orig).atom();
+              let target = match &named_export.src {
+                Some(src) => ExportTarget::Reexport {
+                  source: src.value.to_string(),
+                  imported: orig.to_string(),
+                },
+                None => imports.get(orig).cloned().unwrap_or(ExportTarget::Local),
+              };
+              (exported.to_string(), target)
+            }
+            ExportSpecifier::Namespace(namespace) => {
+              let Some(src) = &named_export.src else {
+                continue;
+              };
+              (
+                namespace.name.atom().to_string(),
+                ExportTarget::Reexport {
+                  source: src.value.to_string(),
+                  imported: "*".to_string(),
+                },
+              )
+            }
+            _ => continue,
+          };
+          exports.named.insert(exported, target);
+        }
+      }
+      ModuleDecl::ExportDecl(export_decl) => {
+        for name in decl_names(&export_decl.decl) {
+          exports.named.insert(name, ExportTarget::Local);
+        }
+      }
+      ModuleDecl::ExportDefaultDecl(_) | ModuleDecl::ExportDefaultExpr(_) => {
+        exports
+          .named
+          .insert("default".to_string(), ExportTarget::Local);
+      }
+      ModuleDecl::ExportAll(export_all) if !export_all.type_only => {
+        exports.wildcards.push(export_all.src.value.to_string());
+      }
+      _ => {}
+    }
+  }
+  exports
+}
+
+fn decl_names(decl: &Decl) -> Vec<String> {
+  match decl {
+    Decl::Class(class_decl) => vec![class_decl.ident.sym.to_string()],
+    Decl::Fn(fn_decl) => vec![fn_decl.ident.sym.to_string()],
+    Decl::Var(var_decl) => var_decl
+      .decls
+      .iter()
+      .flat_map(|decl| pat_names(&decl.name))
+      .collect(),
+    Decl::TsEnum(ts_enum) => vec![ts_enum.id.sym.to_string()],
+    _ => vec![],
+  }
+}
+
+fn pat_names(pat: &Pat) -> Vec<String> {
+  match pat {
+    Pat::Ident(binding) => vec![binding.id.sym.to_string()],
+    Pat::Array(array) => array.elems.iter().flatten().flat_map(pat_names).collect(),
+    Pat::Object(object) => object
+      .props
+      .iter()
+      .flat_map(|prop| match prop {
+        ObjectPatProp::KeyValue(key_value) => pat_names(&key_value.value),
+        ObjectPatProp::Assign(assign) => vec![assign.key.sym.to_string()],
+        ObjectPatProp::Rest(rest) => pat_names(&rest.arg),
+      })
+      .collect(),
+    Pat::Assign(assign) => pat_names(&assign.left),
+    Pat::Rest(rest) => pat_names(&rest.arg),
+    _ => vec![],
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Package directory removed once the test is done with it
+  struct TempPackage(PathBuf);
+
+  impl std::ops::Deref for TempPackage {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+      &self.0
+    }
+  }
+
+  impl Drop for TempPackage {
+    fn drop(&mut self) {
+      let _ = std::fs::remove_dir_all(&self.0);
+    }
+  }
+
+  // Writes `files` under a fresh directory and returns it
+  fn create_package(name: &str, files: &[(&str, &str)]) -> TempPackage {
+    let root = std::env::temp_dir().join(format!("barrel-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    for (file, content) in files {
+      let path = root.join(file);
+      std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+      std::fs::write(path, content).unwrap();
+    }
+    TempPackage(root)
+  }
+
+  fn optimize(root: &Path, names: &str) -> String {
+    let barrel = root.join("index.js");
+    let source = std::fs::read_to_string(&barrel).unwrap();
+    BarrelResolver::default().optimize(&barrel, &source, &parse_names(names))
+  }
+
+  fn request(root: &Path, file: &str) -> String {
+    serde_json::to_string(&path_request(&root.join(file))).unwrap()
+  }
+
+  #[test]
+  fn test_parse_names() {
+    assert_eq!(
+      parse_names("?Input,Button,,Input"),
+      BTreeSet::from(["Button".to_string(), "Input".to_string()])
+    );
+    assert!(parse_names("").is_empty());
+  }
+
+  #[test]
+  fn test_named_reexports() {
+    let root = create_package(
+      "named",
+      &[
+        (
+          "index.js",
+          r#"export { default as Button } from './button';
+export { Input, TextArea as Area } from './input/index.js';
+export * as utils from './utils';"#,
+        ),
+        ("button/index.js", "export default function Button() {}"),
+        (
+          "input/index.js",
+          "export const Input = 1; export const TextArea = 2;",
+        ),
+        ("utils.js", "export const noop = () => {};"),
+      ],
+    );
+
+    let code = optimize(&root, "?Button,Area,utils");
+    assert!(
+      code.contains(&format!(
+        "export {{ default as Button }} from {};",
+        request(&root, "button/index.js")
+      )),
+      "{code}"
+    );
+    assert!(
+      code.contains(&format!(
+        "export {{ TextArea as Area }} from {};",
+        request(&root, "input/index.js")
+      )),
+      "{code}"
+    );
+    assert!(
+      code.contains(&format!(
+        "export * as utils from {};",
+        request(&root, "utils.js")
+      )),
+      "{code}"
+    );
+    assert!(!code.contains("Input }"), "{code}");
+  }
+
+  #[test]
+  fn test_wildcard_chains() {
+    let root = create_package(
+      "wildcard",
+      &[
+        (
+          "index.js",
+          "export * from './components';\nexport * from './hooks';",
+        ),
+        ("components/index.js", "export * from './button';"),
+        ("components/button.js", "export const Button = 1;"),
+        (
+          "hooks.js",
+          "import useState from './state';\nexport { useState };",
+        ),
+        ("state.js", "export default function useState() {}"),
+      ],
+    );
+
+    let code = optimize(&root, "?Button,useState");
+    assert!(
+      code.contains(&format!(
+        "export {{ Button }} from {};",
+        request(&root, "components/button.js")
+      )),
+      "{code}"
+    );
+    assert!(
+      code.contains(&format!(
+        "export {{ default as useState }} from {};",
+        request(&root, "state.js")
+      )),
+      "{code}"
+    );
+  }
+
+  #[test]
+  fn test_untraceable_names() {
+    let root = create_package(
+      "untraceable",
+      &[(
+        "index.js",
+        r#"export const version = '1.0.0';
+export { Icon } from '@ant-design/icons';
+export * from 'other-package';"#,
+      )],
+    );
+
+    let code = optimize(&root, "?Icon,Missing,version");
+    assert!(
+      code.contains(r#"export { Icon } from "@ant-design/icons";"#),
+      "{code}"
+    );
+    // Local declarations and names hidden behind packages stay on the barrel
+    assert!(
+      code.contains(&format!(
+        "export {{ version }} from {};",
+        request(&root, "index.js")
+      )),
+      "{code}"
+    );
+    assert!(
+      code.contains(&format!(
+        "export {{ Missing }} from {};",
+        request(&root, "index.js")
+      )),
+      "{code}"
+    );
+  }
+
+  #[test]
+  fn test_circular_wildcards() {
+    let root = create_package(
+      "circular",
+      &[
+        ("index.js", "export * from './a';"),
+        ("a.js", "export * from './b';\nexport const A = 1;"),
+        ("b.js", "export * from './a';\nexport const B = 1;"),
+      ],
+    );
+
+    let code = optimize(&root, "?B");
+    assert!(
+      code.contains(&format!("export {{ B }} from {};", request(&root, "b.js"))),
+      "{code}"
+    );
+  }
+}
@@ -1,21 +1,28 @@
+mod barrel_optimize;
 mod options;
+mod syntax_check;
 mod transformer;
 mod transforms;
 
-use std::{default::Default, path::Path};
+use std::{collections::BTreeSet, default::Default, path::Path};
 
-use options::CompilationOptionsWithAdditional;
+pub use barrel_optimize::{BarrelOptimizeLoader, BARREL_OPTIMIZE_LOADER_IDENTIFIER};
+use options::{merge_options, CompilationOptionsWithAdditional, TransformFeatures};
 pub use options::CompilationLoaderJsOptions;
 use rspack_cacheable::{cacheable, cacheable_dyn};
 use rspack_core::{Mode, RunnerContext, Loader, LoaderContext};
 use rspack_error::{Diagnostic, Result, miette};
 use rspack_javascript_compiler::{JavaScriptCompiler, TransformOutput};
 use rspack_collections::Identifier;
+use rspack_util::source_map::SourceMapKind;
+use serde::Deserialize;
 use sugar_path::SugarPath;
 use swc_config::{merge::Merge, types::MergingOption};
+use transformer::{ForbiddenImport, SharedTransformReport, TransformReport};
 use swc_core::{
   base::config::{InputSourceMap, TransformConfig},
   common::FileName,
+  ecma::ast::EsVersion,
 };
 
 #[cacheable]
@@ -41,56 +48,43 @@ impl CompilationLoader {
     self
   }
 
-  fn loader_impl(&self, loader_context: &mut LoaderContext<RunnerContext>) -> Result<()> {
-    let resource_path = loader_context
-      .resource_path()
-      .map(|p| p.to_path_buf())
-      .unwrap_or_default();
-    let Some(content) = loader_context.take_content() else {
-      return Ok(());
-    };
-
-    // Check compile rules for exclusion
-    if let Some(exclude_patterns) = &self.options_with_additional.compile_rules.exclude {
-      for pattern in exclude_patterns {
-        let regex = regex::Regex::new(pattern).map_err(|e| {
-          rspack_error::error!("Invalid regex pattern '{}': {}", pattern, e)
-        })?;
-        if regex.is_match(resource_path.as_str()) {
-          // Skip compilation for excluded files, return content as-is
-          let source = content.into_string_lossy();
-          loader_context.finish_with((source, None));
-          return Ok(());
-        }
-      }
-    }
-
+  /// Runs SWC together with the configured transform features over `source`.
+  ///
+  /// This is the part of the loader that does not depend on `LoaderContext`, so it can
+  /// be exercised directly in tests. `transform_features` are the ones resolved for
+  /// `resource_path` by the caller.
+  #[allow(clippy::too_many_arguments)]
+  fn compile(
+    &self,
+    source: String,
+    resource_path: &Path,
+    transform_features: &TransformFeatures,
+    is_development: bool,
+    input_source_map: Option<String>,
+    module_source_map_kind: SourceMapKind,
+    skip_lowering: bool,
+  ) -> Result<(TransformOutput, TransformReport)> {
     let swc_options = {
       let mut swc_options = self.options_with_additional.swc_options.clone();
+      // preset-env also injects the core-js polyfills, which are still needed
+      if skip_lowering && !injects_polyfills(&swc_options.config) {
+        swc_options.config.env = None;
+        swc_options.config.jsc.target = Some(EsVersion::EsNext);
+      }
       if swc_options.config.jsc.transform.as_ref().is_some() {
         let mut transform = TransformConfig::default();
-        transform.react.development =
-          Some(Mode::is_development(&loader_context.context.options.mode));
+        transform.react.development = Some(is_development);
         swc_options
           .config
           .jsc
           .transform
           .merge(MergingOption::from(Some(transform)));
       }
-      if let Some(pre_source_map) = loader_context.source_map().cloned() {
-        if let Ok(source_map) = pre_source_map.to_json() {
-          swc_options.config.input_source_map = Some(InputSourceMap::Str(source_map))
-        }
-      }
-      swc_options.filename = resource_path.as_str().to_string();
-      swc_options.source_file_name = Some(resource_path.as_str().to_string());
-
-      if swc_options.config.jsc.target.is_some() && swc_options.config.env.is_some() {
-        loader_context.emit_diagnostic(Diagnostic::warn(
-          COMPILATION_LOADER_IDENTIFIER.to_string(),
-          "`env` and `jsc.target` cannot be used together".to_string(),
-        ));
+      if let Some(source_map) = input_source_map {
+        swc_options.config.input_source_map = Some(InputSourceMap::Str(source_map))
       }
+      swc_options.filename = resource_path.to_string_lossy().into_owned();
+      swc_options.source_file_name = Some(resource_path.to_string_lossy().into_owned());
 
       #[cfg(feature = "plugin")]
       {
@@ -105,26 +99,97 @@ impl CompilationLoader {
       swc_options
     };
 
+    let report = SharedTransformReport::default();
     let javascript_compiler = JavaScriptCompiler::new();
-    let filename = FileName::Real(resource_path.clone().into_std_path_buf());
+    let filename = FileName::Real(resource_path.to_path_buf());
 
-    let source = content.into_string_lossy();
     let _is_typescript =
       matches!(swc_options.config.jsc.syntax, Some(syntax) if syntax.typescript());
 
-    let TransformOutput {
-      code,
-      mut map,
-      diagnostics,
-    } = javascript_compiler.transform(
+    let output = javascript_compiler.transform(
       source,
       Some(filename),
       swc_options,
-      Some(loader_context.context.module_source_map_kind),
+      Some(module_source_map_kind),
       |_program| {
         // TypeScript info collection could be added here if needed
       },
-      |_| transformer::transform(&self.options_with_additional.transform_features),
+      |_| transformer::transform(transform_features, &report),
+    )?;
+
+    Ok((output, report.take()))
+  }
+
+  fn loader_impl(&self, loader_context: &mut LoaderContext<RunnerContext>) -> Result<()> {
+    let resource_path = loader_context
+      .resource_path()
+      .map(|p| p.to_path_buf())
+      .unwrap_or_default();
+    let Some(content) = loader_context.take_content() else {
+      return Ok(());
+    };
+
+    let source = content.into_string_lossy();
+
+    // Check compile rules for exclusion
+    if self
+      .options_with_additional
+      .compile_rules
+      .is_excluded(resource_path.as_str())
+    {
+      // Skip compilation for excluded files, return content as-is
+      loader_context.finish_with((source, None));
+      return Ok(());
+    }
+
+    let transform_features = self
+      .options_with_additional
+      .transform_features_for(resource_path.as_str())
+      .map_err(|e| rspack_error::error!("Invalid transformFeatures: {}", e))?;
+
+    let swc_config = &self.options_with_additional.swc_options.config;
+    let skip_lowering = !swc_config.minify.into_bool()
+      && self.options_with_additional.compile_rules.is_target_satisfied(
+        resource_path.as_str(),
+        &source,
+        swc_config.module.is_none(),
+      );
+    if skip_lowering && transform_features.is_empty() && !has_non_lowering_transforms(swc_config) {
+      // Nothing to lower and no transforms, keep the source and its source map
+      let source_map = loader_context.take_source_map();
+      loader_context.finish_with((source, source_map));
+      return Ok(());
+    }
+
+    if self.options_with_additional.swc_options.config.jsc.target.is_some()
+      && self.options_with_additional.swc_options.config.env.is_some()
+    {
+      loader_context.emit_diagnostic(Diagnostic::warn(
+        COMPILATION_LOADER_IDENTIFIER.to_string(),
+        "`env` and `jsc.target` cannot be used together".to_string(),
+      ));
+    }
+
+    let input_source_map = loader_context
+      .source_map()
+      .cloned()
+      .and_then(|pre_source_map| pre_source_map.to_json().ok());
+
+    let (
+      TransformOutput {
+        code,
+        mut map,
+        diagnostics,
+      },
+      report,
+    ) = self.compile(
+      source,
+      resource_path.as_std_path(),
+      &transform_features,
+      Mode::is_development(&loader_context.context.options.mode),
+      input_source_map,
+      loader_context.context.module_source_map_kind,
+      skip_lowering,
     )?;
 
     for diagnostic in diagnostics {
@@ -133,6 +198,22 @@ impl CompilationLoader {
       );
     }
 
+    if transform_features.report_removed_exports {
+      if let Some(message) = removed_exports_message(&report) {
+        loader_context.emit_diagnostic(
+          Diagnostic::warn(COMPILATION_LOADER_IDENTIFIER.to_string(), message)
+            .with_file(Some(resource_path.clone())),
+        );
+      }
+    }
+
+    for message in forbidden_imports_messages(&report) {
+      loader_context.emit_diagnostic(
+        Diagnostic::error(COMPILATION_LOADER_IDENTIFIER.to_string(), message)
+          .with_file(Some(resource_path.clone())),
+      );
+    }
+
     // When compiling target modules, SWC retrieves the source map via sourceMapUrl.
     // The sources paths in the source map are relative to the target module. We need to resolve these paths
     // to absolute paths using the resource path to avoid incorrect project path references.
@@ -162,6 +243,67 @@ impl CompilationLoader {
   }
 }
 
+// SWC config that still changes the output when nothing is lowered, so the file has to go
+// through SWC with only `env`/`jsc.target` neutralised
+fn has_non_lowering_transforms(config: &swc_core::base::config::Config) -> bool {
+  let jsc = &config.jsc;
+  injects_polyfills(config)
+    || !jsc.paths.is_empty()
+    || !jsc.base_url.as_os_str().is_empty()
+    || jsc.external_helpers.into_bool()
+    || jsc
+      .experimental
+      .plugins
+      .as_ref()
+      .is_some_and(|plugins| !plugins.is_empty())
+    || jsc
+      .transform
+      .as_ref()
+      .is_some_and(|transform| transform.optimizer.is_some() || transform.const_modules.is_some())
+}
+
+// `env.mode`/`env.coreJs` make preset-env add core-js polyfills whatever the syntax
+fn injects_polyfills(config: &swc_core::base::config::Config) -> bool {
+  config
+    .env
+    .as_ref()
+    .is_some_and(|env| env.mode.is_some() || env.core_js.is_some())
+}
+
+// Rspack diagnostics have no info level, so the report is a warning only emitted on request
+fn removed_exports_message(report: &TransformReport) -> Option<String> {
+  let quote = |names: &BTreeSet<String>| {
+    names
+      .iter()
+      .map(|name| format!("`{name}`"))
+      .collect::<Vec<_>>()
+      .join(", ")
+  };
+  let mut parts = vec![];
+  if !report.removed_exports.is_empty() {
+    parts.push(format!("removed exports {}", quote(&report.removed_exports)));
+  }
+  if !report.removed_imports.is_empty() {
+    parts.push(format!("pruned imports of {}", quote(&report.removed_imports)));
+  }
+  (!parts.is_empty()).then(|| format!("keepExport/removeExport {}", parts.join(" and ")))
+}
+
+fn forbidden_imports_messages(report: &TransformReport) -> Vec<String> {
+  report
+    .forbidden_imports
+    .iter()
+    .map(|ForbiddenImport { source, kept_by }| {
+      let message = format!("`{source}` is listed in removeExport.forbiddenImports but is still imported");
+      if kept_by.is_empty() {
+        message
+      } else {
+        format!("{message}, kept alive by {}", kept_by.join(", "))
+      }
+    })
+    .collect()
+}
+
 pub const COMPILATION_LOADER_IDENTIFIER: &str = "builtin:compilation-loader";
 
 #[cacheable_dyn]
@@ -193,23 +335,72 @@ impl Loader<RunnerContext> for CompilationLoader {
   }
 }
 
-use std::sync::Arc;
+use std::{
+  collections::HashMap,
+  sync::{Arc, RwLock},
+};
 use rspack_core::{NormalModuleFactoryResolveLoader, Plugin, ApplyContext};
 use rspack_hook::{plugin, plugin_hook};
 
+// Loaders keyed by loader request and options, like `SWC_LOADER_CACHE` of `builtin:swc-loader`
+type CompilationLoaderCache = RwLock<HashMap<(String, String), Arc<CompilationLoader>>>;
+
 #[plugin]
 #[derive(Debug)]
-pub struct CompilationLoaderPlugin;
+pub struct CompilationLoaderPlugin {
+  // Loader options every `builtin:compilation-loader` rule inherits, see `merge_options`
+  default_options: Option<serde_json::Value>,
+  // `resolve_loader` runs for every module, so each loader is created once and shared
+  loaders: CompilationLoaderCache,
+}
 
 impl Default for CompilationLoaderPlugin {
   fn default() -> Self {
-    Self::new()
+    Self::new_inner(None, Default::default())
   }
 }
 
 impl CompilationLoaderPlugin {
-  pub fn new() -> Self {
-    Self::new_inner()
+  /// `default_options` must be valid `CompilationLoaderJsOptions`, they are deep-merged
+  /// under the options of each rule using `builtin:compilation-loader`.
+  pub fn new(default_options: Option<serde_json::Value>) -> Result<Self, serde_json::Error> {
+    if let Some(default_options) = &default_options {
+      CompilationLoaderJsOptions::deserialize(default_options)?;
+    }
+    Ok(Self::new_inner(default_options, Default::default()))
+  }
+
+  /// The loader for `request` with the rule's `options`, created on first use.
+  fn loader(
+    &self,
+    request: &str,
+    options: Option<&str>,
+  ) -> Result<Arc<CompilationLoader>, serde_json::Error> {
+    let key = (request.to_string(), options.unwrap_or("{}").to_string());
+    if let Some(loader) = self.loaders.read().expect("lock poisoned").get(&key) {
+      return Ok(loader.clone());
+    }
+    let loader = Arc::new(self.create_loader(Some(&key.1))?.with_identifier(request.into()));
+    Ok(
+      self
+        .loaders
+        .write()
+        .expect("lock poisoned")
+        .entry(key)
+        .or_insert(loader)
+        .clone(),
+    )
+  }
+
+  fn create_loader(&self, options: Option<&str>) -> Result<CompilationLoader, serde_json::Error> {
+    match &self.default_options {
+      // Only runs when `loader` misses its cache, so once per distinct rule options
+      Some(default_options) => Ok(CompilationLoader {
+        identifier: COMPILATION_LOADER_IDENTIFIER.into(),
+        options_with_additional: merge_options(default_options, options)?.try_into()?,
+      }),
+      None => CompilationLoader::new(options.unwrap_or("{}")),
+    }
   }
 }
 
@@ -221,10 +412,15 @@ pub(crate) async fn resolve_loader(
   loader: &rspack_core::ModuleRuleUseLoader,
 ) -> Result<Option<rspack_core::BoxLoader>> {
   if loader.loader.starts_with(COMPILATION_LOADER_IDENTIFIER) {
-    let options = loader.options.clone().unwrap_or_default();
-    let compilation_loader = CompilationLoader::new(&options)
+    let compilation_loader = self
+      .loader(&loader.loader, loader.options.as_deref())
       .map_err(|e| rspack_error::error!("Failed to create CompilationLoader: {}", e))?;
-    return Ok(Some(Arc::new(compilation_loader)));
+    return Ok(Some(compilation_loader));
+  }
+  if loader.loader.starts_with(BARREL_OPTIMIZE_LOADER_IDENTIFIER) {
+    return Ok(Some(Arc::new(
+      BarrelOptimizeLoader::default().with_identifier(loader.loader.as_str().into()),
+    )));
   }
 
   Ok(None)
@@ -242,4 +438,1180 @@ impl Plugin for CompilationLoaderPlugin {
       .tap(resolve_loader::new(self));
     Ok(())
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rspack_core::rspack_sources::SourceMap;
+
+  fn compile_with_source_map(options: &str, source: &str) -> (String, SourceMap) {
+    let loader = CompilationLoader::new(options).expect("invalid loader options");
+    let output = loader
+      .compile(
+        source.to_string(),
+        Path::new("/project/src/index.js"),
+        &loader.options_with_additional.transform_features_for("/project/src/index.js").unwrap(),
+        false,
+        None,
+        SourceMapKind::SourceMap,
+        false,
+      )
+      .expect("failed to compile")
+      .0;
+    (output.code, output.map.expect("missing source map"))
+  }
+
+  // 1-based line and 0-based column of the first occurrence of `needle`
+  fn position_of(text: &str, needle: &str) -> (u32, u32) {
+    let offset = text
+      .find(needle)
+      .unwrap_or_else(|| panic!("`{needle}` not found in:\n{text}"));
+    let before = &text[..offset];
+    let line = before.matches('\n').count() as u32 + 1;
+    let column = (offset - before.rfind('\n').map_or(0, |i| i + 1)) as u32;
+    (line, column)
+  }
+
+  // Asserts the generated `generated` text maps back to where `original` starts in the source
+  fn assert_mapped(options: &str, source: &str, generated: &str, original: &str) {
+    let (code, map) = compile_with_source_map(options, source);
+    let (line, column) = position_of(&code, generated);
+    let mapping = map
+      .decoded_mappings()
+      .find(|m| m.generated_line == line && m.generated_column == column)
+      .unwrap_or_else(|| panic!("no mapping for `{generated}` at {line}:{column} in:\n{code}"));
+    let original_location = mapping
+      .original
+      .unwrap_or_else(|| panic!("`{generated}` has no original position in:\n{code}"));
+    assert_eq!(
+      (original_location.original_line, original_location.original_column),
+      position_of(source, original),
+      "`{generated}` is mapped to the wrong position in:\n{code}"
+    );
+  }
+
+  fn compile(options: &str, source: &str) -> String {
+    let loader = CompilationLoader::new(options).expect("invalid loader options");
+    loader
+      .compile(
+        source.to_string(),
+        Path::new("/project/src/index.js"),
+        &loader.options_with_additional.transform_features_for("/project/src/index.js").unwrap(),
+        false,
+        None,
+        SourceMapKind::empty(),
+        false,
+      )
+      .expect("failed to compile")
+      .0
+      .code
+  }
+
+  #[test]
+  fn test_keep_platform_web() {
+    let code = compile(
+      r#"{ "jsc": { "target": "es2022" }, "transformFeatures": { "keepPlatform": "web" } }"#,
+      r#"import { isWeb, isWeex } from 'universal-env';
+if (isWeb) {
+  console.log('web');
+} else if (isWeex) {
+  console.log('weex');
+}"#,
+    );
+
+    assert!(!code.contains("universal-env"), "{code}");
+    assert!(code.contains("isWeb = true"), "{code}");
+    assert!(code.contains("isWeex = false"), "{code}");
+  }
+
+  #[test]
+  fn test_keep_platform_namespace() {
+    let code = compile(
+      r#"{ "jsc": { "target": "es2022" }, "transformFeatures": { "keepPlatform": "kraken" } }"#,
+      r#"import * as env from '@uni/env';
+console.log(env.isKraken, env.isWeb);"#,
+    );
+
+    assert!(!code.contains("@uni/env"), "{code}");
+    assert!(code.contains("isKraken: true"), "{code}");
+    assert!(code.contains("isWeb: true"), "{code}");
+  }
+
+  #[test]
+  fn test_keep_platform_disabled() {
+    let code = compile(
+      r#"{ "jsc": { "target": "es2022" }, "transformFeatures": { "keepPlatform": false } }"#,
+      r#"import { isWeb } from 'universal-env';
+console.log(isWeb);"#,
+    );
+
+    assert!(code.contains("universal-env"), "{code}");
+  }
+
+  #[test]
+  fn test_node_transform_default_runtime() {
+    let code = compile(
+      r#"{ "jsc": { "target": "es2022" }, "transformFeatures": { "nodeTransform": true } }"#,
+      r#"import { foo } from 'module';
+export const bar = foo + import.meta.url;
+export default import("lazy");"#,
+    );
+
+    assert!(code.contains("await __ice_import__(\"module\")"), "{code}");
+    assert!(code.contains("Object.defineProperty(__ice_exports__, \"bar\""), "{code}");
+    assert!(code.contains("get () {\n        return bar;"), "{code}");
+    assert!(code.contains("__ice_import_meta__.url"), "{code}");
+    assert!(code.contains("__ice_dynamic_import__(\"lazy\")"), "{code}");
+  }
+
+  #[test]
+  fn test_node_transform_custom_runtime() {
+    let code = compile(
+      r#"{
+        "jsc": { "target": "es2022" },
+        "transformFeatures": {
+          "nodeTransform": {
+            "import": "__ssr_import__",
+            "dynamicImport": "__ssr_dynamic_import__",
+            "importMeta": "__ssr_import_meta__",
+            "exports": "__ssr_exports__",
+            "exportsAll": "__ssr_exports_all__"
+          }
+        }
+      }"#,
+      r#"export * from 'module';
+export default import.meta.env;
+import("lazy");"#,
+    );
+
+    assert!(!code.contains("__ice_"), "{code}");
+    assert!(code.contains("await __ssr_import__(\"module\")"), "{code}");
+    assert!(code.contains("__ssr_exports_all__(__ssr_import_0__)"), "{code}");
+    assert!(code.contains("__ssr_exports__.default = __ssr_import_meta__.env"), "{code}");
+    assert!(code.contains("__ssr_dynamic_import__(\"lazy\")"), "{code}");
+  }
+
+  #[test]
+  fn test_change_package_import_mixed_configs() {
+    let code = compile(
+      r#"{
+        "jsc": { "target": "es2022" },
+        "transformFeatures": {
+          "changePackageImport": [
+            "@ice/ui",
+            {
+              "name": "antd",
+              "map": {
+                "Button": { "to": "antd/es/button", "importType": "named", "name": "default" },
+                "Input": { "to": "antd/es/input" }
+              }
+            }
+          ]
+        }
+      }"#,
+      r#"import { Card } from '@ice/ui';
+import { Button, Input } from 'antd';
+console.log(Card, Button, Input);"#,
+    );
+
+    assert!(code.contains("import Card from \"@ice/ui/Card\""), "{code}");
+    assert!(code.contains("import { default as Button } from \"antd/es/button\""), "{code}");
+    assert!(code.contains("import Input from \"antd/es/input\""), "{code}");
+  }
+
+  #[test]
+  fn test_change_package_import_template_config() {
+    let code = compile(
+      r#"{
+        "jsc": { "target": "es2022" },
+        "transformFeatures": {
+          "changePackageImport": [
+            {
+              "source": "antd",
+              "path": "antd/es/{{ kebabCase member }}",
+              "style": "antd/es/{{ kebabCase member }}/style"
+            }
+          ]
+        }
+      }"#,
+      r#"import { DatePicker } from 'antd';
+console.log(DatePicker);"#,
+    );
+
+    assert!(
+      code.contains("import DatePicker from \"antd/es/date-picker\";\nimport \"antd/es/date-picker/style\";"),
+      "{code}"
+    );
+  }
+
+  #[test]
+  fn test_change_package_import_invalid_template() {
+    let error = CompilationLoader::new(
+      r#"{ "transformFeatures": { "changePackageImport": [{ "source": "antd", "path": "antd/{{ dashCase member }}" }] } }"#,
+    )
+    .unwrap_err();
+
+    assert!(
+      error
+        .to_string()
+        .contains("Unknown placeholder '{{ dashCase member }}' in template 'antd/{{ dashCase member }}'"),
+      "{error}"
+    );
+  }
+
+  #[test]
+  fn test_change_package_import_invalid_config() {
+    let error = CompilationLoader::new(
+      r#"{ "transformFeatures": { "changePackageImport": [{ "name": "antd" }] } }"#,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("missing field `map`"), "{error}");
+
+    let error = CompilationLoader::new(
+      r#"{ "transformFeatures": { "changePackageImport": [{ "package": "antd" }] } }"#,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("Invalid `changePackageImport` config"), "{error}");
+  }
+
+  fn compile_with_report(options: &str, source: &str) -> TransformReport {
+    let loader = CompilationLoader::new(options).expect("invalid loader options");
+    loader
+      .compile(
+        source.to_string(),
+        Path::new("/project/src/index.js"),
+        &loader.options_with_additional.transform_features_for("/project/src/index.js").unwrap(),
+        false,
+        None,
+        SourceMapKind::empty(),
+        false,
+      )
+      .expect("failed to compile")
+      .1
+  }
+
+  #[test]
+  fn test_removed_exports_report() {
+    let source = r#"import fs from 'fs';
+import './polyfill';
+import { helper } from './utils';
+export const getData = () => fs.readFileSync('data.json');
+export function getConfig() { return helper(); }
+export { helper as util };
+export default function Page() {}"#;
+
+    let report = compile_with_report(
+      r#"{ "transformFeatures": { "removeExport": ["getData", "util"] } }"#,
+      source,
+    );
+    assert_eq!(
+      report.removed_exports,
+      BTreeSet::from(["getData".to_string(), "util".to_string()])
+    );
+    assert_eq!(report.removed_imports, BTreeSet::from(["fs".to_string()]));
+
+    let report = compile_with_report(
+      r#"{ "transformFeatures": { "keepExport": ["getConfig"] } }"#,
+      source,
+    );
+    assert_eq!(
+      report.removed_exports,
+      BTreeSet::from(["default".to_string(), "getData".to_string(), "util".to_string()])
+    );
+    assert_eq!(
+      report.removed_imports,
+      BTreeSet::from(["./polyfill".to_string(), "fs".to_string()])
+    );
+    assert_eq!(
+      removed_exports_message(&report).unwrap(),
+      "keepExport/removeExport removed exports `default`, `getData`, `util` and pruned imports of `./polyfill`, `fs`"
+    );
+
+    let report = compile_with_report("{}", source);
+    assert!(removed_exports_message(&report).is_none());
+  }
+
+  #[test]
+  fn test_remove_export_forbidden_imports() {
+    let options = r#"{
+      "transformFeatures": {
+        "removeExport": { "exports": ["getServerData"], "forbiddenImports": ["fs"] }
+      }
+    }"#;
+
+    let report = compile_with_report(
+      options,
+      r#"import fs from 'fs';
+export const getServerData = () => fs.readFileSync('data.json');
+export const getConfig = () => fs.existsSync('config.json');"#,
+    );
+    assert_eq!(
+      forbidden_imports_messages(&report),
+      vec!["`fs` is listed in removeExport.forbiddenImports but is still imported, kept alive by export `getConfig`".to_string()]
+    );
+
+    let report = compile_with_report(
+      options,
+      r#"import fs from 'fs';
+export const getServerData = () => fs.readFileSync('data.json');
+export default function Page() {}"#,
+    );
+    assert!(forbidden_imports_messages(&report).is_empty());
+  }
+
+  #[test]
+  fn test_remove_export_shadowed_commonjs_exports() {
+    let options = r#"{
+      "transformFeatures": {
+        "removeExport": { "exports": ["getServerData"], "forbiddenImports": ["fs"] }
+      }
+    }"#;
+
+    // A local `exports` is a plain object, not the module's exports
+    let source = r#"import fs from 'fs';
+const exports = {};
+exports.getServerData = () => fs.readFileSync('data.json');
+console.log(exports);"#;
+    let code = compile(options, source);
+    assert!(code.contains("exports.getServerData"), "{code}");
+    let report = compile_with_report(options, source);
+    assert!(report.removed_exports.is_empty(), "{report:?}");
+    assert_eq!(
+      forbidden_imports_messages(&report),
+      vec!["`fs` is listed in removeExport.forbiddenImports but is still imported, kept alive by top-level code".to_string()]
+    );
+
+    // A parameter named `exports` leaves the top-level CommonJS export alone
+    let source = r#"import fs from 'fs';
+function register(exports) { exports.getServerData = () => null; }
+exports.getServerData = () => fs.readFileSync('data.json');
+register(module.exports);"#;
+    let code = compile(options, source);
+    assert!(!code.contains("fs"), "{code}");
+    assert!(code.contains("function register(exports) {\n    exports.getServerData ="), "{code}");
+    let report = compile_with_report(options, source);
+    assert_eq!(report.removed_exports, BTreeSet::from(["getServerData".to_string()]));
+    assert!(forbidden_imports_messages(&report).is_empty());
+  }
+
+  #[test]
+  fn test_remove_export_invalid_forbidden_imports() {
+    let error = CompilationLoader::new(
+      r#"{ "transformFeatures": { "removeExport": { "exports": ["getData"], "forbiddenImports": ["/(/"] } } }"#,
+    )
+    .unwrap_err();
+
+    assert!(error.to_string().contains("removeExport.forbiddenImports"), "{error}");
+  }
+
+  #[test]
+  fn test_transform_features_overrides() {
+    let loader = CompilationLoader::new(
+      r#"{
+        "jsc": { "target": "es2022" },
+        "transformFeatures": {
+          "removeExport": ["getServerData"],
+          "overrides": [
+            { "test": "/routes/", "removeExport": ["getServerData", "getConfig"] },
+            { "test": "/routes/admin/", "keepPlatform": "web" }
+          ]
+        }
+      }"#,
+    )
+    .unwrap();
+    let source = r#"import { isWeb } from 'universal-env';
+export const getServerData = () => 1;
+export const getConfig = () => isWeb;
+export default function Page() {}"#;
+    let compile_at = |path: &str| {
+      loader
+        .compile(
+          source.to_string(),
+          Path::new(path),
+          &loader.options_with_additional.transform_features_for(path).unwrap(),
+          false,
+          None,
+          SourceMapKind::empty(),
+          false,
+        )
+        .unwrap()
+        .0
+        .code
+    };
+
+    let code = compile_at("/project/src/components/a.js");
+    assert!(!code.contains("getServerData"), "{code}");
+    assert!(code.contains("getConfig"), "{code}");
+
+    let code = compile_at("/project/src/routes/index.js");
+    assert!(!code.contains("getServerData"), "{code}");
+    assert!(!code.contains("getConfig"), "{code}");
+
+    // Every matching override is merged, in order
+    let features = loader
+      .options_with_additional
+      .transform_features_for("/project/src/routes/admin/index.js")
+      .unwrap();
+    assert!(features
+      .remove_export
+      .as_ref()
+      .is_some_and(|config| config.exports.is_match("getConfig")));
+    assert!(features.keep_platform.is_some());
+    assert!(loader
+      .options_with_additional
+      .transform_features
+      .keep_platform
+      .is_none());
+  }
+
+  #[test]
+  fn test_transform_features_overrides_shared_across_modules() {
+    let plugin = CompilationLoaderPlugin::default();
+    let options = r#"{
+      "transformFeatures": {
+        "overrides": [{ "test": "/routes/", "removeExport": ["getServerData"] }]
+      }
+    }"#;
+    let features_for = |path: &str| {
+      plugin
+        .loader(COMPILATION_LOADER_IDENTIFIER, Some(options))
+        .unwrap()
+        .options_with_additional
+        .transform_features_for(path)
+        .unwrap()
+    };
+
+    assert!(Arc::ptr_eq(
+      &features_for("/project/src/routes/index.js"),
+      &features_for("/project/src/routes/about.js")
+    ));
+  }
+
+  #[test]
+  fn test_transform_features_overrides_invalid() {
+    let error = CompilationLoader::new(
+      r#"{ "transformFeatures": { "overrides": [{ "test": "(", "keepPlatform": "web" }] } }"#,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("transformFeatures.overrides"), "{error}");
+
+    let error = CompilationLoader::new(
+      r#"{ "transformFeatures": { "overrides": [{ "test": "routes", "removeExport": ["/(/"] }] } }"#,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("keepExport/removeExport"), "{error}");
+
+    let error = CompilationLoader::new(
+      r#"{ "transformFeatures": { "overrides": [{ "test": "routes", "overrides": [] }] } }"#,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("cannot be nested"), "{error}");
+  }
+
+  #[test]
+  fn test_export_selector_patterns() {
+    let code = compile(
+      r#"{
+        "jsc": { "target": "es2022" },
+        "transformFeatures": { "keepExport": ["default", { "glob": "get*Data" }, { "regex": "^unstable_" }] }
+      }"#,
+      r#"export const getServerData = () => 1;
+export const getConfig = () => 2;
+export const unstable_settings = {};
+export default function Page() {}"#,
+    );
+
+    assert!(code.contains("getServerData"), "{code}");
+    assert!(code.contains("unstable_settings"), "{code}");
+    assert!(code.contains("Page"), "{code}");
+    assert!(!code.contains("getConfig"), "{code}");
+  }
+
+  #[test]
+  fn test_export_selector_invalid_pattern() {
+    let error = CompilationLoader::new(
+      r#"{ "transformFeatures": { "removeExport": ["/(/"] } }"#,
+    )
+    .unwrap_err();
+
+    assert!(error.to_string().contains("keepExport/removeExport"), "{error}");
+  }
+
+  #[test]
+  fn test_plugin_default_options() {
+    let plugin = CompilationLoaderPlugin::new(Some(serde_json::json!({
+      "jsc": { "target": "es2022" },
+      "compileRules": { "exclude": ["node_modules"] },
+      "transformFeatures": { "keepPlatform": "web" }
+    })))
+    .unwrap();
+    let loader = plugin
+      .create_loader(Some(r#"{ "transformFeatures": { "removeExport": ["getData"] } }"#))
+      .unwrap();
+    let features = &loader.options_with_additional.transform_features;
+
+    assert!(features.keep_platform.is_some());
+    assert!(features
+      .remove_export
+      .as_ref()
+      .is_some_and(|config| config.exports.is_match("getData")));
+    assert!(loader
+      .options_with_additional
+      .compile_rules
+      .is_excluded("/project/node_modules/react/index.js"));
+
+    // The merged options are what the persistent cache restores the loader from
+    use rspack_cacheable::with::AsRefStrConverter;
+    let restored = CompilationOptionsWithAdditional::from_str(loader.options_with_additional.as_str());
+    assert!(restored.transform_features.keep_platform.is_some());
+    assert!(restored.transform_features.remove_export.is_some());
+  }
+
+  #[test]
+  fn test_plugin_rule_options_override_defaults() {
+    let plugin = CompilationLoaderPlugin::new(Some(serde_json::json!({
+      "compileRules": { "exclude": ["node_modules"] },
+      "transformFeatures": { "keepPlatform": "web" }
+    })))
+    .unwrap();
+    let loader = plugin
+      .create_loader(Some(
+        r#"{ "compileRules": { "exclude": ["vendor"] }, "transformFeatures": { "keepPlatform": false } }"#,
+      ))
+      .unwrap();
+
+    assert!(matches!(
+      loader.options_with_additional.transform_features.keep_platform,
+      Some(transforms::keep_platform::KeepPlatformConfig::Bool(false))
+    ));
+    let compile_rules = &loader.options_with_additional.compile_rules;
+    assert!(compile_rules.is_excluded("/project/vendor/index.js"));
+    assert!(!compile_rules.is_excluded("/project/node_modules/react/index.js"));
+  }
+
+  #[test]
+  fn test_plugin_without_rule_options() {
+    let plugin = CompilationLoaderPlugin::new(Some(serde_json::json!({
+      "transformFeatures": { "keepPlatform": "web" }
+    })))
+    .unwrap();
+
+    assert!(plugin
+      .create_loader(None)
+      .unwrap()
+      .options_with_additional
+      .transform_features
+      .keep_platform
+      .is_some());
+    assert!(CompilationLoaderPlugin::default().create_loader(None).is_ok());
+  }
+
+  #[test]
+  fn test_plugin_reuses_loaders() {
+    let plugin = CompilationLoaderPlugin::new(Some(serde_json::json!({
+      "transformFeatures": { "keepPlatform": "web" }
+    })))
+    .unwrap();
+    let options = r#"{ "transformFeatures": { "removeExport": ["getData"] } }"#;
+    let loader = plugin.loader(COMPILATION_LOADER_IDENTIFIER, Some(options)).unwrap();
+
+    assert!(Arc::ptr_eq(
+      &loader,
+      &plugin.loader(COMPILATION_LOADER_IDENTIFIER, Some(options)).unwrap()
+    ));
+    assert!(!Arc::ptr_eq(
+      &loader,
+      &plugin.loader(COMPILATION_LOADER_IDENTIFIER, None).unwrap()
+    ));
+    assert!(Arc::ptr_eq(
+      &plugin.loader(COMPILATION_LOADER_IDENTIFIER, None).unwrap(),
+      &plugin.loader(COMPILATION_LOADER_IDENTIFIER, Some("{}")).unwrap()
+    ));
+    let request = format!("{COMPILATION_LOADER_IDENTIFIER}??ruleSet[1].rules[0]");
+    let other = plugin.loader(&request, Some(options)).unwrap();
+    assert!(!Arc::ptr_eq(&loader, &other));
+    assert_eq!(other.identifier().as_str(), request);
+    assert_eq!(plugin.loaders.read().unwrap().len(), 3);
+  }
+
+  #[test]
+  fn test_plugin_invalid_default_options() {
+    assert!(CompilationLoaderPlugin::new(Some(serde_json::json!({
+      "transformFeatures": { "removeExport": "getData" }
+    })))
+    .is_err());
+  }
+
+  #[test]
+  fn test_compile_rules_exclude() {
+    let loader = CompilationLoader::new(
+      r#"{ "compileRules": { "exclude": ["node_modules/react/", "\\.min\\.js$"] } }"#,
+    )
+    .unwrap();
+    let matcher = &loader.options_with_additional.compile_rules;
+
+    assert!(matcher.is_excluded("/project/node_modules/react/index.js"));
+    assert!(matcher.is_excluded("/project/src/vendor.min.js"));
+    assert!(!matcher.is_excluded("/project/src/index.js"));
+  }
+
+  #[test]
+  fn test_compile_rules_invalid_exclude() {
+    let error = CompilationLoader::new(
+      r#"{ "compileRules": { "exclude": ["node_modules", "src/(index"] } }"#,
+    )
+    .unwrap_err();
+
+    assert!(
+      error
+        .to_string()
+        .contains("Invalid pattern 'src/(index' in `compileRules.exclude`"),
+      "{error}"
+    );
+  }
+
+  #[test]
+  fn test_compile_rules_include_overrides_exclude() {
+    let loader = CompilationLoader::new(
+      r#"{
+        "compileRules": {
+          "exclude": [{ "glob": "**/node_modules/**" }],
+          "include": [{ "glob": "**/node_modules/@ali/**" }, "node_modules/lodash-es/"]
+        }
+      }"#,
+    )
+    .unwrap();
+    let compile_rules = &loader.options_with_additional.compile_rules;
+
+    assert!(compile_rules.is_excluded("/project/node_modules/react/index.js"));
+    assert!(compile_rules.is_excluded("/project/node_modules/.pnpm/a@1.0.0/node_modules/a/index.js"));
+    assert!(!compile_rules.is_excluded("/project/node_modules/@ali/ui/es/index.js"));
+    assert!(!compile_rules.is_excluded("/project/node_modules/lodash-es/get.js"));
+    assert!(!compile_rules.is_excluded("/project/src/index.js"));
+  }
+
+  #[test]
+  fn test_compile_rules_include_without_exclude() {
+    let loader = CompilationLoader::new(
+      r#"{ "compileRules": { "include": [{ "glob": "src/**" }] } }"#,
+    )
+    .unwrap();
+
+    // `include` only re-includes excluded files, it never excludes anything itself
+    assert!(!loader
+      .options_with_additional
+      .compile_rules
+      .is_excluded("/project/node_modules/react/index.js"));
+  }
+
+  #[test]
+  fn test_compile_rules_relative_glob() {
+    let loader = CompilationLoader::new(
+      r#"{
+        "compileRules": {
+          "exclude": ["node_modules"],
+          "include": [{ "glob": "node_modules/@ali/**" }]
+        }
+      }"#,
+    )
+    .unwrap();
+    let compile_rules = &loader.options_with_additional.compile_rules;
+
+    assert!(!compile_rules.is_excluded("/project/node_modules/@ali/ui/index.js"));
+    assert!(!compile_rules.is_excluded("C:\\project\\node_modules\\@ali\\ui\\index.js"));
+    assert!(compile_rules.is_excluded("/project/node_modules/react/index.js"));
+    assert!(compile_rules.is_excluded("/project/node_modules/x/node_modules_@ali/index.js"));
+  }
+
+  #[test]
+  fn test_compile_rules_glob() {
+    let loader = CompilationLoader::new(
+      r#"{ "compileRules": { "exclude": [{ "glob": "**/vendor/*.js" }, { "glob": "**/lib?.js" }] } }"#,
+    )
+    .unwrap();
+    let compile_rules = &loader.options_with_additional.compile_rules;
+
+    assert!(compile_rules.is_excluded("/project/vendor/jquery.js"));
+    assert!(compile_rules.is_excluded("C:\\project\\vendor\\jquery.js"));
+    assert!(!compile_rules.is_excluded("/project/vendor/nested/jquery.js"));
+    assert!(!compile_rules.is_excluded("/project/vendor/jquery.jsx"));
+    assert!(compile_rules.is_excluded("/project/lib1.js"));
+    assert!(!compile_rules.is_excluded("/project/lib10.js"));
+  }
+
+  #[test]
+  fn test_compile_rules_exclude_packages() {
+    let root = std::env::temp_dir().join(format!("compile-rules-{}", std::process::id()));
+    let react = root.join("node_modules/.pnpm/react@18.2.0/node_modules/react");
+    let ui = root.join("node_modules/@ice/ui");
+    for (dir, package_json) in [
+      (&root, r#"{ "name": "app" }"#),
+      (&react, r#"{ "name": "react" }"#),
+      (&ui, r#"{ "name": "@ice/ui" }"#),
+      (&ui.join("es"), r#"{ "sideEffects": false }"#),
+    ] {
+      std::fs::create_dir_all(dir).unwrap();
+      std::fs::write(dir.join("package.json"), package_json).unwrap();
+    }
+
+    let loader = CompilationLoader::new(
+      r#"{ "compileRules": { "excludePackages": ["react", "@ice/ui"], "include": ["/ui/es/button/"] } }"#,
+    )
+    .unwrap();
+    let compile_rules = &loader.options_with_additional.compile_rules;
+    let is_excluded = |path: std::path::PathBuf| compile_rules.is_excluded(path.to_str().unwrap());
+
+    assert!(is_excluded(react.join("cjs/react.development.js")));
+    assert!(is_excluded(react.join("index.js")));
+    assert!(is_excluded(ui.join("es/input/index.js")));
+    assert!(!is_excluded(ui.join("es/button/index.js")));
+    assert!(!is_excluded(root.join("src/index.js")));
+
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn test_compile_rules_package_names_shared_across_modules() {
+    let root = std::env::temp_dir().join(format!("compile-rules-shared-{}", std::process::id()));
+    let react = root.join("node_modules/react");
+    std::fs::create_dir_all(react.join("cjs")).unwrap();
+    std::fs::write(react.join("package.json"), r#"{ "name": "react" }"#).unwrap();
+
+    let plugin = CompilationLoaderPlugin::default();
+    let options = r#"{ "compileRules": { "excludePackages": ["react"] } }"#;
+    let is_excluded = |path: std::path::PathBuf| {
+      plugin
+        .loader(COMPILATION_LOADER_IDENTIFIER, Some(options))
+        .unwrap()
+        .options_with_additional
+        .compile_rules
+        .is_excluded(path.to_str().unwrap())
+    };
+
+    assert!(is_excluded(react.join("cjs/react.development.js")));
+    // A second module of the package is answered from the directory cached by the first
+    std::fs::write(react.join("package.json"), r#"{ "name": "not-react" }"#).unwrap();
+    assert!(is_excluded(react.join("cjs/react.production.js")));
+    assert!(is_excluded(react.join("index.js")));
+
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn test_skip_if_target_satisfied() {
+    let loader = CompilationLoader::new(
+      r#"{ "jsc": { "target": "es2015" }, "compileRules": { "skipIfTargetSatisfied": true } }"#,
+    )
+    .unwrap();
+    let compile_rules = &loader.options_with_additional.compile_rules;
+    let source = "const a = () => 1;";
+
+    assert!(compile_rules.is_target_satisfied("/project/node_modules/a/index.js", source, true));
+    assert!(!compile_rules.is_target_satisfied("/project/src/index.js", source, true));
+    assert!(!compile_rules.is_target_satisfied(
+      "/project/node_modules/a/index.js",
+      "const a = b ?? c;",
+      true
+    ));
+    assert!(!CompilationLoader::new(r#"{ "jsc": { "target": "es2015" } }"#)
+      .unwrap()
+      .options_with_additional
+      .compile_rules
+      .is_target_satisfied("/project/node_modules/a/index.js", source, true));
+  }
+
+  #[test]
+  fn test_skip_lowering_keeps_non_lowering_transforms() {
+    let options = r#"{
+      "jsc": {
+        "target": "es5",
+        "transform": { "optimizer": { "globals": { "vars": { "__DEV__": "false" } } } }
+      },
+      "compileRules": { "skipIfTargetSatisfied": true }
+    }"#;
+    let loader = CompilationLoader::new(options).unwrap();
+    assert!(has_non_lowering_transforms(&loader.options_with_additional.swc_options.config));
+
+    let code = loader
+      .compile(
+        "const a = () => __DEV__;".to_string(),
+        Path::new("/project/node_modules/a/index.js"),
+        &loader.options_with_additional.transform_features_for("/project/node_modules/a/index.js").unwrap(),
+        false,
+        None,
+        SourceMapKind::empty(),
+        true,
+      )
+      .unwrap()
+      .0
+      .code;
+    assert!(code.contains("const a = ()=>false"), "{code}");
+
+    for options in [
+      r#"{ "jsc": { "externalHelpers": true } }"#,
+      r#"{ "jsc": { "transform": { "constModules": { "globals": {} } } } }"#,
+    ] {
+      let loader = CompilationLoader::new(options).unwrap();
+      assert!(has_non_lowering_transforms(&loader.options_with_additional.swc_options.config));
+    }
+    for options in ["{}", r#"{ "jsc": { "transform": { "react": { "runtime": "automatic" } } } }"#] {
+      let loader = CompilationLoader::new(options).unwrap();
+      assert!(!has_non_lowering_transforms(&loader.options_with_additional.swc_options.config));
+    }
+  }
+
+  fn compile_skipping_lowering(loader: &CompilationLoader, resource_path: &str, source: &str) -> String {
+    loader
+      .compile(
+        source.to_string(),
+        Path::new(resource_path),
+        &loader.options_with_additional.transform_features_for(resource_path).unwrap(),
+        false,
+        None,
+        SourceMapKind::empty(),
+        true,
+      )
+      .unwrap()
+      .0
+      .code
+  }
+
+  #[test]
+  fn test_skip_lowering_keeps_polyfills() {
+    let loader = CompilationLoader::new(
+      r#"{
+        "env": { "targets": "chrome 60", "mode": "usage", "coreJs": "3.30" },
+        "compileRules": { "skipIfTargetSatisfied": true }
+      }"#,
+    )
+    .unwrap();
+    assert!(has_non_lowering_transforms(&loader.options_with_additional.swc_options.config));
+
+    let code = compile_skipping_lowering(
+      &loader,
+      "/project/node_modules/a/index.js",
+      "Promise.allSettled([]);",
+    );
+    assert!(code.contains("core-js/modules/es.promise.all-settled"), "{code}");
+
+    let loader = CompilationLoader::new(r#"{ "env": { "targets": "chrome 60" } }"#).unwrap();
+    assert!(!has_non_lowering_transforms(&loader.options_with_additional.swc_options.config));
+  }
+
+  #[test]
+  fn test_skip_lowering_keeps_paths() {
+    // The import rewriter runs for `jsc.paths`/`jsc.baseUrl`, so those files are not bypassed
+    for jsc in [
+      serde_json::json!({ "baseUrl": "/project", "paths": { "@/*": ["src/*"] } }),
+      serde_json::json!({ "baseUrl": "/project" }),
+      serde_json::json!({ "paths": { "@/*": ["/project/src/*"] } }),
+    ] {
+      let loader = CompilationLoader::new(
+        &serde_json::json!({ "jsc": jsc, "compileRules": { "skipIfTargetSatisfied": true } })
+          .to_string(),
+      )
+      .unwrap();
+      assert!(
+        has_non_lowering_transforms(&loader.options_with_additional.swc_options.config),
+        "{jsc}"
+      );
+    }
+  }
+
+  #[test]
+  fn test_disabled_transform_features_are_empty() {
+    let loader = CompilationLoader::new(
+      r#"{ "transformFeatures": { "keepPlatform": false, "nodeTransform": false } }"#,
+    )
+    .unwrap();
+    assert!(loader.options_with_additional.transform_features.is_empty());
+
+    let loader = CompilationLoader::new(r#"{ "transformFeatures": { "nodeTransform": true } }"#)
+      .unwrap();
+    assert!(!loader.options_with_additional.transform_features.is_empty());
+  }
+
+  #[test]
+  fn test_skip_lowering_keeps_transform_features() {
+    let loader = CompilationLoader::new(
+      r#"{
+        "jsc": { "target": "es5" },
+        "compileRules": { "skipIfTargetSatisfied": true },
+        "transformFeatures": { "keepPlatform": "web" }
+      }"#,
+    )
+    .unwrap();
+    let code = loader
+      .compile(
+        r#"import { isWeb } from 'universal-env';
+const a = () => isWeb;"#
+          .to_string(),
+        Path::new("/project/node_modules/a/index.js"),
+        &loader.options_with_additional.transform_features_for("/project/node_modules/a/index.js").unwrap(),
+        false,
+        None,
+        SourceMapKind::empty(),
+        true,
+      )
+      .unwrap()
+      .0
+      .code;
+
+    assert!(code.contains("isWeb = true"), "{code}");
+    assert!(code.contains("const a = ()=>isWeb"), "{code}");
+  }
+
+  #[test]
+  fn test_env_replacement_custom_flags() {
+    let code = compile(
+      r#"{
+        "transformFeatures": {
+          "envReplacement": {
+            "sources": ["universal-env"],
+            "flags": { "isInHouse": { "renderer": "client", "globals": ["inHouse"] } }
+          }
+        }
+      }"#,
+      r#"import { isInHouse, isWeb } from 'universal-env';
+console.log(isInHouse, isWeb);"#,
+    );
+
+    assert!(
+      code.contains(r#"var isInHouse = import.meta.renderer === "client" && typeof inHouse !== "undefined";"#),
+      "{code}"
+    );
+    assert!(
+      code.contains(r#"var isWeb = import.meta.renderer === "client" && import.meta.target === "web";"#),
+      "{code}"
+    );
+  }
+
+  #[test]
+  fn test_env_replacement_static_flags() {
+    let code = compile(
+      r#"{
+        "jsc": { "target": "es2015" },
+        "transformFeatures": {
+          "envReplacement": { "sources": ["universal-env"], "renderer": "client", "target": "web" }
+        }
+      }"#,
+      r#"import { isWeb, isWeex, isPHA } from 'universal-env';
+if (isWeb) {
+  console.log('web');
+} else {
+  console.log('not web');
+}
+if (isWeex || false) {
+  var weexOnly = 1;
+  console.log('weex');
+}
+const renderer = !isWeex ? 'web' : 'weex';
+if (isPHA) {
+  console.log('pha');
+}
+function later() {
+  if (!isWeb) console.log('not web');
+}"#,
+    );
+
+    assert!(code.contains("var isWeb = true;"), "{code}");
+    assert!(code.contains("console.log('web')"), "{code}");
+    assert!(!code.contains("not web"), "{code}");
+    assert!(!code.contains("console.log('weex')"), "{code}");
+    assert!(code.contains("var weexOnly;"), "{code}");
+    assert!(code.contains("const renderer = 'web';"), "{code}");
+    assert!(code.contains("typeof pha === \"object\""), "{code}");
+    assert!(code.contains("console.log('pha')"), "{code}");
+  }
+
+  #[test]
+  fn test_env_replacement_static_flags_in_function_body() {
+    let code = compile(
+      r#"{
+        "jsc": { "target": "es2015" },
+        "transformFeatures": {
+          "envReplacement": { "sources": ["universal-env"], "renderer": "client", "target": "web" }
+        }
+      }"#,
+      r#"import { isWeb, isWeex } from 'universal-env';
+export function f() {
+  const x = isWeex ? 'weex' : 'web';
+  const g = () => {
+    if (isWeb) {
+      console.log('web');
+    } else {
+      console.log('not web');
+    }
+  };
+  return [x, g];
+}"#,
+    );
+
+    assert!(code.contains("const x = 'web';"), "{code}");
+    assert!(code.contains("console.log('web')"), "{code}");
+    assert!(!code.contains("not web"), "{code}");
+  }
+
+  const ENV_OPTIONS: &str = r#"{
+    "jsc": { "target": "es2015" },
+    "transformFeatures": {
+      "envReplacement": { "sources": ["universal-env"], "renderer": "client" }
+    }
+  }"#;
+
+  #[test]
+  fn test_env_replacement_namespace_member() {
+    let code = compile(
+      ENV_OPTIONS,
+      r#"import * as env from 'universal-env';
+import universal from 'universal-env';
+console.log(env.isWeb, universal['isClient'], env.isServer);"#,
+    );
+
+    assert!(!code.contains("isWeex"), "{code}");
+    assert!(
+      code.contains(r#"console.log(import.meta.target === "web", true, false);"#),
+      "{code}"
+    );
+  }
+
+  #[test]
+  fn test_env_replacement_namespace_escaping() {
+    let code = compile(
+      ENV_OPTIONS,
+      r#"import * as env from 'universal-env';
+console.log(env.isClient, Object.keys(env));"#,
+    );
+
+    assert!(code.contains("const env = {"), "{code}");
+    assert!(code.contains("console.log(true, Object.keys(env));"), "{code}");
+  }
+
+  #[test]
+  fn test_env_replacement_require_member() {
+    let code = compile(
+      ENV_OPTIONS,
+      r#"const env = require('universal-env');
+if (env.isServer) {
+  console.log('server');
+}"#,
+    );
+
+    assert!(!code.contains("env"), "{code}");
+    assert!(!code.contains("server"), "{code}");
+  }
+
+  #[test]
+  fn test_env_replacement_reexport() {
+    let code = compile(
+      ENV_OPTIONS,
+      r#"export { isClient, isWeb as web, default as env } from 'universal-env';
+export * as platform from 'universal-env';"#,
+    );
+
+    assert!(!code.contains("universal-env"), "{code}");
+    assert!(code.contains("var __env_isClient__ = true;"), "{code}");
+    assert!(
+      code.contains(r#"var __env_isWeb__ = import.meta.target === "web";"#),
+      "{code}"
+    );
+    assert!(
+      code.contains("export { __env_isClient__ as isClient, __env_isWeb__ as web, __env__ as env };"),
+      "{code}"
+    );
+    assert!(code.contains("export { __env__ as platform };"), "{code}");
+    assert_eq!(code.matches("__env__ = {").count(), 1, "{code}");
+
+    // Re-exporting the same flag or the flags object again reuses the local declaration
+    let code = compile(
+      ENV_OPTIONS,
+      r#"export { default as a, default as b, isWeb, isWeb as web } from 'universal-env';
+export * as platform from 'universal-env';"#,
+    );
+    assert_eq!(code.matches("__env__ = {").count(), 1, "{code}");
+    assert_eq!(code.matches("var __env_isWeb__ =").count(), 1, "{code}");
+    assert!(
+      code.contains("export { __env__ as a, __env__ as b, __env_isWeb__ as isWeb, __env_isWeb__ as web };"),
+      "{code}"
+    );
+    assert!(code.contains("export { __env__ as platform };"), "{code}");
+  }
+
+  #[test]
+  fn test_env_replacement_export_all() {
+    let code = compile(
+      ENV_OPTIONS,
+      r#"export * from 'universal-env';
+export const isWeex = false;"#,
+    );
+
+    assert!(!code.contains("universal-env"), "{code}");
+    assert!(code.contains("var __env_isClient__ = true;"), "{code}");
+    assert!(code.contains("var __env_isServer__ = false;"), "{code}");
+    assert!(code.contains("__env_isClient__ as isClient"), "{code}");
+    assert!(code.contains("__env_isServer__ as isServer"), "{code}");
+    // The module's own export wins over the re-exported flag
+    assert!(!code.contains("__env_isWeex__"), "{code}");
+    assert!(!code.contains("as default"), "{code}");
+  }
+
+  #[test]
+  fn test_env_replacement_non_flag_members() {
+    let code = compile(
+      ENV_OPTIONS,
+      r#"import * as env from 'universal-env';
+console.log(env.isClient, env.hasOwnProperty('isWeb'), env.toString, env['constructor']);"#,
+    );
+
+    assert!(code.contains("const env = {"), "{code}");
+    assert!(
+      code.contains(r#"console.log(true, env.hasOwnProperty('isWeb'), env.toString, env['constructor']);"#),
+      "{code}"
+    );
+  }
+
+  const SOURCE_MAP_SOURCE: &str = r#"const before = 1;
+import { isWeb } from 'universal-env';
+import { Button } from 'antd';
+export const value = isWeb ? Button : null;"#;
+
+  #[test]
+  fn test_source_map_env_replacement() {
+    assert_mapped(
+      r#"{ "jsc": { "target": "es2022" }, "transformFeatures": { "envReplacement": ["universal-env"] } }"#,
+      SOURCE_MAP_SOURCE,
+      "var isWeb",
+      "isWeb }",
+    );
+  }
+
+  #[test]
+  fn test_source_map_keep_platform() {
+    assert_mapped(
+      r#"{ "jsc": { "target": "es2022" }, "transformFeatures": { "keepPlatform": "web" } }"#,
+      SOURCE_MAP_SOURCE,
+      "var isWeb",
+      "import { isWeb }",
+    );
+  }
+
+  #[test]
+  fn test_source_map_change_package_import() {
+    assert_mapped(
+      r#"{ "jsc": { "target": "es2022" }, "transformFeatures": { "changePackageImport": ["antd"] } }"#,
+      SOURCE_MAP_SOURCE,
+      "import Button from \"antd/Button\"",
+      "Button }",
+    );
+  }
+
+  #[test]
+  fn test_source_map_node_transform() {
+    let options =
+      r#"{ "jsc": { "target": "es2022" }, "transformFeatures": { "nodeTransform": true } }"#;
+    assert_mapped(options, SOURCE_MAP_SOURCE, "const __ice_import_0__", "import { isWeb }");
+    assert_mapped(options, SOURCE_MAP_SOURCE, "const isWeb", "isWeb }");
+    assert_mapped(options, SOURCE_MAP_SOURCE, "Object.defineProperty", "value =");
+  }
+}
@@ -2,40 +2,556 @@ use rspack_cacheable::{
   cacheable,
   with::{AsRefStr, AsRefStrConverter},
 };
-use serde::Deserialize;
+use std::{
+  collections::{HashMap, HashSet},
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+};
+
+use regex::RegexSet;
+use serde::{de::Error as _, Deserialize};
 use swc_config::{file_pattern::FilePattern, types::BoolConfig};
-use swc_core::base::config::{
-  Config, ErrorConfig, FileMatcher, InputSourceMap, IsModule, JscConfig, ModuleConfig, Options,
-  SourceMapsConfig,
+use swc_core::{
+  base::config::{
+    Config, ErrorConfig, FileMatcher, InputSourceMap, IsModule, JscConfig, ModuleConfig, Options,
+    SourceMapsConfig,
+  },
+  ecma::ast::EsVersion,
+};
+
+use crate::syntax_check::{is_target_satisfied, SyntaxTarget};
+use crate::transforms::{
+  change_package_import::{SpecificConfigs, TemplateConfig},
+  env_replacement::EnvReplacementConfig, keep_platform::KeepPlatformConfig,
+  named_import_transform::PackageMatcher,
+  node_transform::NodeTransformConfig,
 };
 
 // Compile rules for excluding files from compilation
+//
+// A file is left as-is when it matches `exclude` or belongs to one of `excludePackages`,
+// and does not match `include`. So `include` can opt specific packages back in, e.g.
+// excluding `node_modules` while including `{ "glob": "**/node_modules/@ali/**" }`.
 #[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct CompileRules {
   // Built-in rules to exclude files from compilation, such as react, react-dom, etc.
-  pub exclude: Option<Vec<String>>,
+  pub exclude: Option<Vec<CompileRulePattern>>,
+  // Files to compile even though they match `exclude`.
+  pub include: Option<Vec<CompileRulePattern>>,
+  // Package names, resolved from the nearest package.json, whose files are not compiled.
+  pub exclude_packages: Option<Vec<String>>,
+  // Skip lowering node_modules files that use no syntax above `jsc.target`/`env`.
+  pub skip_if_target_satisfied: bool,
+}
+
+// Pattern matched against the resource path, either a regex string or `{ "glob": "..." }`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum CompileRulePattern {
+  Regex(String),
+  Glob { glob: String },
+}
+
+impl CompileRulePattern {
+  fn to_regex(&self) -> String {
+    match self {
+      CompileRulePattern::Regex(regex) => regex.clone(),
+      // Relative globs such as `src/**` match at any depth of the absolute resource path
+      CompileRulePattern::Glob { glob } if !is_absolute_glob(glob) => {
+        glob_to_regex(&format!("**/{glob}"))
+      }
+      CompileRulePattern::Glob { glob } => glob_to_regex(glob),
+    }
+  }
+}
+
+fn is_absolute_glob(glob: &str) -> bool {
+  glob.starts_with(['/', '\\'])
+    || glob.starts_with("**")
+    || glob.as_bytes().get(1) == Some(&b':')
+}
+
+impl std::fmt::Display for CompileRulePattern {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CompileRulePattern::Regex(regex) => write!(f, "{regex}"),
+      CompileRulePattern::Glob { glob } => write!(f, "{glob}"),
+    }
+  }
+}
+
+/// Translates a glob into an anchored regex matching the whole path.
+///
+/// `**` matches any number of path segments, `*` and `?` match within a single
+/// segment, and `/` matches both path separators so globs work on Windows too.
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+  let mut regex = String::from("^");
+  let mut chars = glob.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '*' if chars.peek() == Some(&'*') => {
+        chars.next();
+        if chars.peek() == Some(&'/') {
+          chars.next();
+          regex.push_str(r"(?:.*[/\\])?");
+        } else {
+          regex.push_str(".*");
+        }
+      }
+      '*' => regex.push_str(r"[^/\\]*"),
+      '?' => regex.push_str(r"[^/\\]"),
+      '/' => regex.push_str(r"[/\\]"),
+      c => regex.push_str(&regex::escape(&c.to_string())),
+    }
+  }
+  regex.push('$');
+  regex
+}
+
+// A name, `{ "glob": "..." }` or `{ "regex": "..." }`.
+// Plain names containing `*` or `?` are globs, and `/.../` strings are regexes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum NamePattern {
+  Name(String),
+  Glob { glob: String },
+  Regex { regex: String },
+}
+
+/// Exact names looked up first, with the patterns compiled once into a single `RegexSet`.
+#[derive(Debug, Clone, Default)]
+pub struct NameMatcher {
+  names: HashSet<String>,
+  patterns: Option<RegexSet>,
+}
+
+impl NameMatcher {
+  pub(crate) fn new(patterns: Vec<NamePattern>, option: &str) -> Result<Self, String> {
+    let mut names = HashSet::new();
+    let mut regexes = vec![];
+    for pattern in patterns {
+      let regex = match pattern {
+        NamePattern::Name(name) if name.len() > 1 && name.starts_with('/') && name.ends_with('/') => {
+          name[1..name.len() - 1].to_string()
+        }
+        NamePattern::Name(name) if !name.contains(['*', '?']) => {
+          names.insert(name);
+          continue;
+        }
+        NamePattern::Name(glob) | NamePattern::Glob { glob } => glob_to_regex(&glob),
+        NamePattern::Regex { regex } => regex,
+      };
+      if let Err(error) = regex::Regex::new(&regex) {
+        return Err(format!("Invalid pattern '{regex}' in `{option}`: {error}"));
+      }
+      regexes.push(regex);
+    }
+    let patterns = if regexes.is_empty() {
+      None
+    } else {
+      Some(RegexSet::new(&regexes).map_err(|error| error.to_string())?)
+    };
+    Ok(Self { names, patterns })
+  }
+
+  pub fn is_match(&self, name: &str) -> bool {
+    self.names.contains(name)
+      || self
+        .patterns
+        .as_ref()
+        .is_some_and(|patterns| patterns.is_match(name))
+  }
+}
+
+/// Exports selected by `keepExport`/`removeExport`, e.g. `getServerData`, `get*Data`
+/// or `/^unstable_/`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "Vec<NamePattern>")]
+pub struct ExportSelector(NameMatcher);
+
+impl ExportSelector {
+  pub fn is_match(&self, name: &str) -> bool {
+    self.0.is_match(name)
+  }
+}
+
+impl TryFrom<Vec<NamePattern>> for ExportSelector {
+  type Error = String;
+
+  fn try_from(exports: Vec<NamePattern>) -> Result<Self, Self::Error> {
+    NameMatcher::new(exports, "keepExport/removeExport").map(Self)
+  }
+}
+
+// Plain export names in tests, where a pattern that fails to compile is a bug in the test
+#[cfg(test)]
+impl From<Vec<String>> for ExportSelector {
+  fn from(exports: Vec<String>) -> Self {
+    let exports = exports.into_iter().map(NamePattern::Name).collect::<Vec<_>>();
+    Self::try_from(exports).expect("export patterns should compile")
+  }
+}
+
+/// `removeExport`, either the exports to remove or `{ exports, forbiddenImports }`.
+///
+/// Imports matching `forbiddenImports` must not survive the removal, e.g. server-only
+/// modules that should never reach the client bundle.
+#[derive(Debug, Clone, Default)]
+pub struct RemoveExportConfig {
+  pub exports: ExportSelector,
+  pub forbidden_imports: Option<NameMatcher>,
+}
+
+// Not `untagged`, which would hide the invalid pattern errors behind a generic message
+impl<'de> Deserialize<'de> for RemoveExportConfig {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase", deny_unknown_fields)]
+    struct Options {
+      exports: ExportSelector,
+      forbidden_imports: Option<Vec<NamePattern>>,
+    }
+
+    let value = serde_json::Value::deserialize(deserializer)?;
+    if value.is_array() {
+      let exports = ExportSelector::deserialize(value).map_err(D::Error::custom)?;
+      return Ok(Self {
+        exports,
+        forbidden_imports: None,
+      });
+    }
+    let Options {
+      exports,
+      forbidden_imports,
+    } = Options::deserialize(value).map_err(D::Error::custom)?;
+    let forbidden_imports = forbidden_imports
+      .map(|patterns| NameMatcher::new(patterns, "removeExport.forbiddenImports"))
+      .transpose()
+      .map_err(D::Error::custom)?;
+    Ok(Self {
+      exports,
+      forbidden_imports,
+    })
+  }
+}
+
+// Compile rules with their patterns compiled once per loader, instead of once per module
+#[derive(Debug, Default)]
+pub(crate) struct CompileRulesMatcher {
+  exclude: Option<RegexSet>,
+  include: Option<RegexSet>,
+  exclude_packages: HashSet<String>,
+  // Name of the package owning each directory looked up so far
+  package_names: Mutex<HashMap<PathBuf, Option<String>>>,
+  // Set when `skipIfTargetSatisfied` is enabled
+  syntax_target: Option<SyntaxTarget>,
+}
+
+impl CompileRulesMatcher {
+  fn new(
+    compile_rules: &CompileRules,
+    target: Option<EsVersion>,
+    env: Option<&swc_core::ecma::preset_env::Config>,
+  ) -> Result<Self, serde_json::Error> {
+    let exclude = compile_rules
+      .exclude
+      .as_deref()
+      .map(|patterns| compile_regex_set(patterns, "compileRules.exclude"))
+      .transpose()?;
+    let include = compile_rules
+      .include
+      .as_deref()
+      .map(|patterns| compile_regex_set(patterns, "compileRules.include"))
+      .transpose()?;
+    Ok(Self {
+      exclude,
+      include,
+      exclude_packages: compile_rules
+        .exclude_packages
+        .iter()
+        .flatten()
+        .cloned()
+        .collect(),
+      package_names: Default::default(),
+      syntax_target: compile_rules
+        .skip_if_target_satisfied
+        .then(|| SyntaxTarget::new(target, env)),
+    })
+  }
+
+  /// Whether `source` is a node_modules file that uses no syntax the target needs
+  /// lowered, so it can skip the SWC compat passes.
+  pub(crate) fn is_target_satisfied(
+    &self,
+    resource_path: &str,
+    source: &str,
+    allow_module_decls: bool,
+  ) -> bool {
+    let Some(syntax_target) = &self.syntax_target else {
+      return false;
+    };
+    (resource_path.contains("/node_modules/") || resource_path.contains("\\node_modules\\"))
+      && is_target_satisfied(source, syntax_target, allow_module_decls)
+  }
+
+  pub(crate) fn is_excluded(&self, resource_path: &str) -> bool {
+    if self
+      .include
+      .as_ref()
+      .is_some_and(|include| include.is_match(resource_path))
+    {
+      return false;
+    }
+    if self
+      .exclude
+      .as_ref()
+      .is_some_and(|exclude| exclude.is_match(resource_path))
+    {
+      return true;
+    }
+    !self.exclude_packages.is_empty()
+      && self
+        .package_name(Path::new(resource_path))
+        .is_some_and(|name| self.exclude_packages.contains(&name))
+  }
+
+  /// Name of the package owning `resource_path`, read from the nearest package.json
+  /// that has a `name`. Nested manifests such as `es/package.json` usually do not.
+  fn package_name(&self, resource_path: &Path) -> Option<String> {
+    let mut visited = vec![];
+    let mut package_name = None;
+    let mut dir = resource_path.parent();
+    while let Some(current) = dir {
+      if let Some(cached) = self.package_names.lock().expect("lock poisoned").get(current) {
+        package_name = cached.clone();
+        break;
+      }
+      visited.push(current.to_path_buf());
+      if let Some(name) = read_package_name(&current.join("package.json")) {
+        package_name = Some(name);
+        break;
+      }
+      dir = current.parent();
+    }
+
+    let mut package_names = self.package_names.lock().expect("lock poisoned");
+    for dir in visited {
+      package_names.insert(dir, package_name.clone());
+    }
+    package_name
+  }
+}
+
+fn read_package_name(package_json: &Path) -> Option<String> {
+  #[derive(Deserialize)]
+  struct PackageJson {
+    name: Option<String>,
+  }
+
+  let content = std::fs::read_to_string(package_json).ok()?;
+  serde_json::from_str::<PackageJson>(&content).ok()?.name
+}
+
+fn compile_regex_set(
+  patterns: &[CompileRulePattern],
+  option: &str,
+) -> Result<RegexSet, serde_json::Error> {
+  let regexes = patterns
+    .iter()
+    .map(CompileRulePattern::to_regex)
+    .collect::<Vec<_>>();
+  RegexSet::new(&regexes).map_err(|_| {
+    // Compile the patterns one by one to report the offending one
+    let (pattern, error) = patterns
+      .iter()
+      .zip(&regexes)
+      .find_map(|(pattern, regex)| regex::Regex::new(regex).err().map(|e| (pattern, e)))
+      .expect("RegexSet fails only if one of its patterns is invalid");
+    serde_json::Error::custom(format!(
+      "Invalid pattern '{pattern}' in `{option}`: {error}"
+    ))
+  })
 }
 
 // Transform feature options for custom transformations
 #[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct TransformFeatures {
-  pub env_replacement: Option<Vec<String>>,
-  pub keep_export: Option<Vec<String>>,
-  pub remove_export: Option<Vec<String>>,
+  // Either the env sources, or `{ sources, flags }` with flags added to the built-in table
+  pub env_replacement: Option<EnvReplacementConfig>,
+  // Export names, globs such as `get*Data`, or regexes such as `/^unstable_/`
+  pub keep_export: Option<ExportSelector>,
+  // Either the exports, or `{ exports, forbiddenImports }` to fail on imports still kept
+  pub remove_export: Option<RemoveExportConfig>,
   pub named_import_transform: Option<NamedImportTransformConfig>,
   pub change_package_import: Option<Vec<ChangeConfig>>,
+  // Either `false` to disable, or the platform name (e.g. "web", "weex") whose code is kept
+  pub keep_platform: Option<KeepPlatformConfig>,
+  // Either `true`, or the names of the runtime helpers used by the server-side sandbox
+  pub node_transform: Option<NodeTransformConfig>,
+  // Warn with the exports and imports keepExport/removeExport dropped from each module
+  pub report_removed_exports: bool,
+  // Features merged over these ones for the files matching `test`, taken out once parsed
+  pub overrides: Vec<TransformFeaturesOverride>,
 }
 
+// `{ test: "<regex>", ...features }`, matched against the resource path
 #[derive(Debug, Deserialize)]
-pub struct NamedImportTransformConfig {
-  pub packages: Vec<String>,
+pub struct TransformFeaturesOverride {
+  pub test: String,
+  #[serde(flatten)]
+  pub features: serde_json::Map<String, serde_json::Value>,
+}
+
+/// `transformFeatures.overrides` compiled once per loader, with the features resolved
+/// for each combination of matching overrides cached.
+#[derive(Debug, Default)]
+pub(crate) struct TransformFeaturesOverrides {
+  tests: Option<RegexSet>,
+  // The base `transformFeatures`, without `overrides`
+  base: serde_json::Value,
+  overrides: Vec<serde_json::Value>,
+  resolved: Mutex<HashMap<Vec<usize>, Arc<TransformFeatures>>>,
+}
+
+impl TransformFeaturesOverrides {
+  fn new(
+    base: serde_json::Value,
+    overrides: Vec<TransformFeaturesOverride>,
+  ) -> Result<Self, serde_json::Error> {
+    if overrides.is_empty() {
+      return Ok(Self::default());
+    }
+    if overrides.iter().any(|o| o.features.contains_key("overrides")) {
+      return Err(serde_json::Error::custom(
+        "`transformFeatures.overrides` cannot be nested",
+      ));
+    }
+    let tests = RegexSet::new(overrides.iter().map(|o| &o.test)).map_err(|error| {
+      serde_json::Error::custom(format!("Invalid `transformFeatures.overrides` test: {error}"))
+    })?;
+    let overrides = overrides
+      .into_iter()
+      .map(|o| serde_json::Value::Object(o.features))
+      .collect::<Vec<_>>();
+    let matcher = Self {
+      tests: Some(tests),
+      base,
+      overrides,
+      resolved: Default::default(),
+    };
+    // Surface invalid features when the loader is created, not on the first matching file
+    for index in 0..matcher.overrides.len() {
+      matcher.resolve(vec![index])?;
+    }
+    Ok(matcher)
+  }
+
+  /// Features for `resource_path`, or `None` when no override matches it.
+  pub(crate) fn resolve_for(
+    &self,
+    resource_path: &str,
+  ) -> Result<Option<Arc<TransformFeatures>>, serde_json::Error> {
+    let Some(tests) = &self.tests else {
+      return Ok(None);
+    };
+    let matched = tests.matches(resource_path).into_iter().collect::<Vec<_>>();
+    if matched.is_empty() {
+      return Ok(None);
+    }
+    self.resolve(matched).map(Some)
+  }
+
+  fn resolve(&self, matched: Vec<usize>) -> Result<Arc<TransformFeatures>, serde_json::Error> {
+    if let Some(features) = self.resolved.lock().unwrap().get(&matched) {
+      return Ok(features.clone());
+    }
+    let mut features = self.base.clone();
+    for &index in &matched {
+      merge_json(&mut features, self.overrides[index].clone());
+    }
+    let features = Arc::new(serde_json::from_value::<TransformFeatures>(features)?);
+    self.resolved.lock().unwrap().insert(matched, features.clone());
+    Ok(features)
+  }
+}
+
+impl TransformFeatures {
+  pub(crate) fn is_empty(&self) -> bool {
+    let TransformFeatures {
+      env_replacement,
+      keep_export,
+      remove_export,
+      named_import_transform,
+      change_package_import,
+      keep_platform,
+      node_transform,
+      report_removed_exports: _,
+      overrides: _,
+    } = self;
+    env_replacement.is_none()
+      && keep_export.is_none()
+      && remove_export.is_none()
+      && named_import_transform.is_none()
+      && change_package_import.is_none()
+      && keep_platform
+        .as_ref()
+        .is_none_or(|config| matches!(config, KeepPlatformConfig::Bool(false)))
+      && node_transform
+        .as_ref()
+        .is_none_or(|config| matches!(config, NodeTransformConfig::Bool(false)))
+  }
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamedImportTransformConfig {
+  // Package names, globs such as `@ali/ui-*`, or `{ "regex": "..." }`
+  pub packages: PackageMatcher,
+  // Split named specifiers out of imports that also have default or namespace ones,
+  // and turn namespace imports only read as `ns.foo` into named imports
+  #[serde(default)]
+  pub mixed_imports: bool,
+}
+
+// Either a package name whose named imports become `<name>/<Export>` default imports,
+// `{ name, map }` describing where each export is imported from,
+// or `{ source, path, style, importType }` with path templates rendered per export
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum ChangeConfig {
   LiteralConfig(String),
+  SpecificConfig(SpecificConfigs),
+  TemplateConfig(TemplateConfig),
+}
+
+// Not `untagged`, which would hide the invalid template errors behind a generic message
+impl<'de> Deserialize<'de> for ChangeConfig {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    let config = match &value {
+      serde_json::Value::String(source) => return Ok(Self::LiteralConfig(source.clone())),
+      serde_json::Value::Object(config) if config.contains_key("source") => {
+        TemplateConfig::deserialize(value).map(Self::TemplateConfig)
+      }
+      serde_json::Value::Object(config) if config.contains_key("name") => {
+        SpecificConfigs::deserialize(value).map(Self::SpecificConfig)
+      }
+      _ => {
+        return Err(D::Error::custom(
+          "Invalid `changePackageImport` config, expected a package name, `{ name, map }` or `{ source, path }`",
+        ))
+      }
+    };
+    config.map_err(D::Error::custom)
+  }
 }
 
 // Raw options from JavaScript side
@@ -100,8 +616,24 @@ pub struct CompilationLoaderJsOptions {
 pub(crate) struct CompilationOptionsWithAdditional {
   raw_options: String,
   pub(crate) swc_options: Options,
-  pub(crate) compile_rules: CompileRules,
-  pub(crate) transform_features: TransformFeatures,
+  pub(crate) compile_rules: CompileRulesMatcher,
+  pub(crate) transform_features: Arc<TransformFeatures>,
+  pub(crate) transform_features_overrides: TransformFeaturesOverrides,
+}
+
+impl CompilationOptionsWithAdditional {
+  /// `transformFeatures` with the overrides matching `resource_path` merged in.
+  pub(crate) fn transform_features_for(
+    &self,
+    resource_path: &str,
+  ) -> Result<Arc<TransformFeatures>, serde_json::Error> {
+    Ok(
+      self
+        .transform_features_overrides
+        .resolve_for(resource_path)?
+        .unwrap_or_else(|| self.transform_features.clone()),
+    )
+  }
 }
 
 impl AsRefStrConverter for CompilationOptionsWithAdditional {
@@ -119,7 +651,22 @@ const SOURCE_MAP_INLINE: &str = "inline";
 impl TryFrom<&str> for CompilationOptionsWithAdditional {
   type Error = serde_json::Error;
   fn try_from(value: &str) -> Result<Self, Self::Error> {
-    let option: CompilationLoaderJsOptions = serde_json::from_str(value)?;
+    Self::from_json(serde_json::from_str(value)?, value.into())
+  }
+}
+
+// Options merged by `merge_options`, which are only serialized for the persistent cache
+impl TryFrom<serde_json::Value> for CompilationOptionsWithAdditional {
+  type Error = serde_json::Error;
+  fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+    let raw_options = value.to_string();
+    Self::from_json(value, raw_options)
+  }
+}
+
+impl CompilationOptionsWithAdditional {
+  fn from_json(mut value: serde_json::Value, raw_options: String) -> Result<Self, serde_json::Error> {
+    let option = CompilationLoaderJsOptions::deserialize(&value)?;
     let CompilationLoaderJsOptions {
       source_maps,
       source_map,
@@ -150,8 +697,26 @@ impl TryFrom<&str> for CompilationOptionsWithAdditional {
       }
     }
     
+    let compile_rules =
+      CompileRulesMatcher::new(&compile_rules.unwrap_or_default(), jsc.target, env.as_ref())?;
+
+    let mut transform_features = transform_features.unwrap_or_default();
+    let overrides = std::mem::take(&mut transform_features.overrides);
+    let transform_features_overrides = if overrides.is_empty() {
+      TransformFeaturesOverrides::default()
+    } else {
+      let mut base = value
+        .get_mut("transformFeatures")
+        .map(serde_json::Value::take)
+        .unwrap_or_default();
+      if let Some(base) = base.as_object_mut() {
+        base.remove("overrides");
+      }
+      TransformFeaturesOverrides::new(base, overrides)?
+    };
+
     Ok(CompilationOptionsWithAdditional {
-      raw_options: value.into(),
+      raw_options,
       swc_options: Options {
         config: Config {
           env,
@@ -171,8 +736,40 @@ impl TryFrom<&str> for CompilationOptionsWithAdditional {
         },
         ..Default::default()
       },
-      compile_rules: compile_rules.unwrap_or_default(),
-      transform_features: transform_features.unwrap_or_default(),
+      compile_rules,
+      transform_features: Arc::new(transform_features),
+      transform_features_overrides,
     })
   }
-}
\ No newline at end of file
+}
+
+/// Deep-merges `value` over `base`. Objects are merged key by key, any other value
+/// (arrays included) replaces the base one.
+fn merge_json(base: &mut serde_json::Value, value: serde_json::Value) {
+  match (base, value) {
+    (serde_json::Value::Object(base), serde_json::Value::Object(value)) => {
+      for (key, value) in value {
+        match base.get_mut(&key) {
+          Some(base) => merge_json(base, value),
+          None => {
+            base.insert(key, value);
+          }
+        }
+      }
+    }
+    (base, value) => *base = value,
+  }
+}
+
+/// Deep-merges the rule's loader `options` over the plugin-level `default_options`,
+/// the same way as `merge_json`.
+pub(crate) fn merge_options(
+  default_options: &serde_json::Value,
+  options: Option<&str>,
+) -> Result<serde_json::Value, serde_json::Error> {
+  let mut merged = default_options.clone();
+  if let Some(options) = options.filter(|options| !options.trim().is_empty()) {
+    merge_json(&mut merged, serde_json::from_str(options)?);
+  }
+  Ok(merged)
+}
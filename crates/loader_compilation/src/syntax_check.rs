@@ -0,0 +1,346 @@
+use std::{collections::HashSet, sync::Arc};
+
+use swc_core::{
+  common::{FileName, SourceMap},
+  ecma::{
+    ast::*,
+    parser::{lexer::Lexer, EsSyntax, Parser, StringInput, Syntax},
+    preset_env::{self, Caniuse, EnvConfig, Feature, FeatureConfig},
+    visit::{Visit, VisitWith},
+  },
+};
+
+// The syntax level a compilation lowers to, from `jsc.target` or `env`
+#[derive(Debug)]
+pub(crate) enum SyntaxTarget {
+  EsVersion(EsVersion),
+  Env(Arc<FeatureConfig>),
+}
+
+impl SyntaxTarget {
+  pub(crate) fn new(target: Option<EsVersion>, env: Option<&preset_env::Config>) -> Self {
+    match (env, target) {
+      (Some(env), _) => SyntaxTarget::Env(EnvConfig::from(env.clone()).get_feature_config()),
+      // SWC lowers to ES5 when no target is configured
+      (None, target) => SyntaxTarget::EsVersion(target.unwrap_or(EsVersion::Es5)),
+    }
+  }
+}
+
+impl Caniuse for SyntaxTarget {
+  fn caniuse(&self, feature: Feature) -> bool {
+    match self {
+      SyntaxTarget::EsVersion(version) => version.caniuse(feature),
+      SyntaxTarget::Env(config) => config.caniuse(feature),
+    }
+  }
+}
+
+/// Returns true when `source` parses as plain JavaScript and uses no syntax that SWC
+/// would lower for `target`, so the file can be emitted without running the compiler.
+///
+/// Files containing JSX, TypeScript or other non-standard syntax fail to parse and are
+/// never considered satisfied. Module declarations only pass when `allow_module_decls`
+/// is set, i.e. no `module` transform is configured.
+pub(crate) fn is_target_satisfied(
+  source: &str,
+  target: &SyntaxTarget,
+  allow_module_decls: bool,
+) -> bool {
+  let cm = SourceMap::default();
+  let fm = cm.new_source_file(FileName::Anon.into(), source.to_string());
+  let lexer = Lexer::new(
+    Syntax::Es(EsSyntax::default()),
+    EsVersion::EsNext,
+    StringInput::from(&*fm),
+    None,
+  );
+  let mut parser = Parser::new_from(lexer);
+  let Ok(program) = parser.parse_program() else {
+    return false;
+  };
+  if !parser.take_errors().is_empty() {
+    return false;
+  }
+
+  let mut collector = SyntaxFeatureCollector::default();
+  program.visit_with(&mut collector);
+
+  (allow_module_decls || !collector.has_module_decls)
+    && collector
+      .features
+      .into_iter()
+      .all(|feature| target.caniuse(feature))
+}
+
+// Collects the preset-env features needed to lower the syntax used by a program.
+//
+// Features that SWC applies to almost any code without changing its meaning for the
+// target, such as `typeof` symbol checks and function names, are not tracked.
+#[derive(Default)]
+struct SyntaxFeatureCollector {
+  features: HashSet<Feature>,
+  has_module_decls: bool,
+}
+
+impl Visit for SyntaxFeatureCollector {
+  fn visit_module_decl(&mut self, n: &ModuleDecl) {
+    self.has_module_decls = true;
+    n.visit_children_with(self);
+  }
+
+  fn visit_export_namespace_specifier(&mut self, n: &ExportNamespaceSpecifier) {
+    self.features.insert(Feature::ExportNamespaceFrom);
+    n.visit_children_with(self);
+  }
+
+  fn visit_arrow_expr(&mut self, n: &ArrowExpr) {
+    self.features.insert(Feature::ArrowFunctions);
+    if n.is_async {
+      self.features.insert(Feature::AsyncToGenerator);
+    }
+    n.visit_children_with(self);
+  }
+
+  fn visit_function(&mut self, n: &Function) {
+    match (n.is_async, n.is_generator) {
+      (true, true) => {
+        self.features.insert(Feature::AsyncGeneratorFunctions);
+      }
+      (true, false) => {
+        self.features.insert(Feature::AsyncToGenerator);
+      }
+      (false, true) => {
+        self.features.insert(Feature::Regenerator);
+      }
+      (false, false) => {}
+    }
+    n.visit_children_with(self);
+  }
+
+  fn visit_class(&mut self, n: &Class) {
+    self.features.insert(Feature::Classes);
+    n.visit_children_with(self);
+  }
+
+  fn visit_class_member(&mut self, n: &ClassMember) {
+    match n {
+      ClassMember::ClassProp(_) | ClassMember::PrivateProp(_) => {
+        self.features.insert(Feature::ClassProperties);
+      }
+      ClassMember::PrivateMethod(_) => {
+        self.features.insert(Feature::PrivateMethods);
+      }
+      ClassMember::StaticBlock(_) => {
+        self.features.insert(Feature::ClassStaticBlock);
+      }
+      _ => {}
+    }
+    n.visit_children_with(self);
+  }
+
+  fn visit_var_decl(&mut self, n: &VarDecl) {
+    if n.kind != VarDeclKind::Var {
+      self.features.insert(Feature::BlockScoping);
+    }
+    n.visit_children_with(self);
+  }
+
+  fn visit_tpl(&mut self, n: &Tpl) {
+    self.features.insert(Feature::TemplateLiterals);
+    n.visit_children_with(self);
+  }
+
+  fn visit_array_pat(&mut self, n: &ArrayPat) {
+    self.features.insert(Feature::Destructuring);
+    n.visit_children_with(self);
+  }
+
+  fn visit_object_pat(&mut self, n: &ObjectPat) {
+    self.features.insert(Feature::Destructuring);
+    if n
+      .props
+      .iter()
+      .any(|prop| matches!(prop, ObjectPatProp::Rest(_)))
+    {
+      self.features.insert(Feature::ObjectRestSpread);
+    }
+    n.visit_children_with(self);
+  }
+
+  fn visit_assign_pat(&mut self, n: &AssignPat) {
+    self.features.insert(Feature::Parameters);
+    n.visit_children_with(self);
+  }
+
+  fn visit_rest_pat(&mut self, n: &RestPat) {
+    self.features.insert(Feature::Parameters);
+    n.visit_children_with(self);
+  }
+
+  fn visit_expr_or_spread(&mut self, n: &ExprOrSpread) {
+    if n.spread.is_some() {
+      self.features.insert(Feature::Spread);
+    }
+    n.visit_children_with(self);
+  }
+
+  fn visit_prop_or_spread(&mut self, n: &PropOrSpread) {
+    if n.is_spread() {
+      self.features.insert(Feature::ObjectRestSpread);
+    }
+    n.visit_children_with(self);
+  }
+
+  fn visit_prop(&mut self, n: &Prop) {
+    if matches!(n, Prop::Shorthand(_) | Prop::Method(_)) {
+      self.features.insert(Feature::ShorthandProperties);
+    }
+    n.visit_children_with(self);
+  }
+
+  fn visit_computed_prop_name(&mut self, n: &ComputedPropName) {
+    self.features.insert(Feature::ComputedProperties);
+    n.visit_children_with(self);
+  }
+
+  fn visit_for_of_stmt(&mut self, n: &ForOfStmt) {
+    self.features.insert(if n.is_await {
+      Feature::AsyncGeneratorFunctions
+    } else {
+      Feature::ForOf
+    });
+    n.visit_children_with(self);
+  }
+
+  fn visit_meta_prop_expr(&mut self, n: &MetaPropExpr) {
+    if n.kind == MetaPropKind::NewTarget {
+      self.features.insert(Feature::NewTarget);
+    }
+  }
+
+  fn visit_super(&mut self, _: &Super) {
+    self.features.insert(Feature::ObjectSuper);
+  }
+
+  fn visit_bin_expr(&mut self, n: &BinExpr) {
+    match n.op {
+      BinaryOp::Exp => {
+        self.features.insert(Feature::ExponentiationOperator);
+      }
+      BinaryOp::NullishCoalescing => {
+        self.features.insert(Feature::NullishCoalescing);
+      }
+      BinaryOp::In if n.left.is_private_name() => {
+        self.features.insert(Feature::PrivatePropertyInObject);
+      }
+      _ => {}
+    }
+    n.visit_children_with(self);
+  }
+
+  fn visit_assign_expr(&mut self, n: &AssignExpr) {
+    match n.op {
+      AssignOp::ExpAssign => {
+        self.features.insert(Feature::ExponentiationOperator);
+      }
+      AssignOp::AndAssign | AssignOp::OrAssign | AssignOp::NullishAssign => {
+        self.features.insert(Feature::LogicalAssignmentOperators);
+      }
+      _ => {}
+    }
+    n.visit_children_with(self);
+  }
+
+  fn visit_opt_chain_expr(&mut self, n: &OptChainExpr) {
+    self.features.insert(Feature::OptionalChaining);
+    n.visit_children_with(self);
+  }
+
+  fn visit_catch_clause(&mut self, n: &CatchClause) {
+    if n.param.is_none() {
+      self.features.insert(Feature::OptionalCatchBinding);
+    }
+    n.visit_children_with(self);
+  }
+
+  fn visit_number(&mut self, n: &Number) {
+    if n.raw.as_ref().is_some_and(|raw| raw.contains('_')) {
+      self.features.insert(Feature::NumericSeparator);
+    }
+  }
+
+  fn visit_regex(&mut self, n: &Regex) {
+    for (flag, feature) in [
+      ('s', Feature::DotAllRegex),
+      ('y', Feature::StickyRegex),
+      ('u', Feature::UnicodeRegex),
+      ('v', Feature::UnicodeSetsRegex),
+    ] {
+      if n.flags.contains(flag) {
+        self.features.insert(feature);
+      }
+    }
+    let exp = n.exp.as_str();
+    if exp.contains("(?<") && exp.replace("(?<=", "").replace("(?<!", "").contains("(?<") {
+      self.features.insert(Feature::NamedCapturingGroupsRegex);
+    }
+    if exp.contains("\\p{") || exp.contains("\\P{") {
+      self.features.insert(Feature::UnicodePropertyRegex);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn satisfied(source: &str, target: EsVersion) -> bool {
+    is_target_satisfied(source, &SyntaxTarget::EsVersion(target), true)
+  }
+
+  #[test]
+  fn test_es5_source() {
+    let source = r#"var a = function (b) { return b ? [1, 2] : { c: 3 }; };
+try { a(); } catch (e) {}
+module.exports = a;"#;
+    assert!(satisfied(source, EsVersion::Es5));
+  }
+
+  #[test]
+  fn test_syntax_above_target() {
+    assert!(!satisfied("const a = 1;", EsVersion::Es5));
+    assert!(satisfied("const a = 1;", EsVersion::Es2015));
+    assert!(!satisfied("var a = b?.c;", EsVersion::Es2019));
+    assert!(satisfied("var a = b?.c;", EsVersion::Es2020));
+    assert!(!satisfied("var a = { ...b };", EsVersion::Es2017));
+    assert!(!satisfied("a ||= b;", EsVersion::Es2020));
+    assert!(!satisfied("class A { #b = 1; }", EsVersion::Es2021));
+    assert!(!satisfied("var a = /(?<year>\\d+)/;", EsVersion::Es2017));
+    assert!(satisfied("var a = /(?<=\\$)\\d+/;", EsVersion::Es5));
+    assert!(!satisfied("try { a(); } catch { }", EsVersion::Es2018));
+  }
+
+  #[test]
+  fn test_unparsable_source() {
+    assert!(!satisfied("var a = <div />;", EsVersion::EsNext));
+    assert!(!satisfied("var a: number = 1;", EsVersion::EsNext));
+  }
+
+  #[test]
+  fn test_module_decls() {
+    let target = SyntaxTarget::EsVersion(EsVersion::Es2015);
+    assert!(is_target_satisfied("export var a = 1;", &target, true));
+    assert!(!is_target_satisfied("export var a = 1;", &target, false));
+  }
+
+  #[test]
+  fn test_env_targets() {
+    let env: preset_env::Config =
+      serde_json::from_str(r#"{ "targets": "chrome 85" }"#).unwrap();
+    let target = SyntaxTarget::new(None, Some(&env));
+    assert!(is_target_satisfied("var a = b ?? c;", &target, true));
+    assert!(is_target_satisfied("a ??= b;", &target, true));
+    assert!(!is_target_satisfied("class A { static { } }", &target, true));
+  }
+}
@@ -1,41 +1,94 @@
+use std::{cell::RefCell, collections::BTreeSet, rc::Rc};
+
 use swc_core::ecma::ast::Pass;
 use crate::options::{TransformFeatures, ChangeConfig};
 use crate::transforms::{
   env_replacement::env_replacement,
   keep_export::keep_export,
   remove_export::remove_export,
+  forbidden_imports::forbidden_imports,
   named_import_transform::{named_import_transform, TransformConfig},
   change_package_import::{change_package_import, Config},
+  keep_platform::{keep_platform, KeepPlatformConfig},
+  node_transform::{node_transform, NodeTransformConfig},
 };
 
-pub(crate) fn transform(transform_features: &TransformFeatures) -> impl Pass + '_ {
+/// What `keepExport`/`removeExport` dropped from a module, collected while folding.
+#[derive(Debug, Default)]
+pub struct TransformReport {
+  pub removed_exports: BTreeSet<String>,
+  // Sources of the imports dropped as a whole
+  pub removed_imports: BTreeSet<String>,
+  // Imports matching `removeExport.forbiddenImports` that are still imported after the removal
+  pub forbidden_imports: Vec<ForbiddenImport>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ForbiddenImport {
+  pub source: String,
+  // What still references the import, e.g. "export `getConfig`" or "top-level code"
+  pub kept_by: Vec<String>,
+}
+
+pub type SharedTransformReport = Rc<RefCell<TransformReport>>;
+
+pub(crate) fn transform<'a>(
+  transform_features: &'a TransformFeatures,
+  report: &SharedTransformReport,
+) -> impl Pass + 'a {
   // Chain transforms based on enabled features
   let mut passes: Vec<Box<dyn Pass>> = Vec::new();
+
+  // Platform flags are folded to constants first, so env_replacement never sees them
+  if let Some(config) = &transform_features.keep_platform {
+    if !matches!(config, KeepPlatformConfig::Bool(false)) {
+      passes.push(Box::new(keep_platform(config.clone())));
+    }
+  }
   
-  if let Some(sources) = &transform_features.env_replacement {
-    passes.push(Box::new(env_replacement(sources.clone())));
+  if let Some(config) = &transform_features.env_replacement {
+    passes.push(Box::new(env_replacement(config.clone())));
   }
   
   if let Some(exports) = &transform_features.keep_export {
-    passes.push(Box::new(keep_export(exports.clone())));
+    passes.push(Box::new(keep_export(exports.clone(), report.clone())));
   }
   
-  if let Some(exports) = &transform_features.remove_export {
-    passes.push(Box::new(remove_export(exports.clone())));
+  if let Some(config) = &transform_features.remove_export {
+    passes.push(Box::new(remove_export(config.exports.clone(), report.clone())));
+    if let Some(forbidden) = &config.forbidden_imports {
+      passes.push(Box::new(forbidden_imports(forbidden.clone(), report.clone())));
+    }
   }
   
   if let Some(config) = &transform_features.named_import_transform {
     passes.push(Box::new(named_import_transform(TransformConfig {
       packages: config.packages.clone(),
+      mixed_imports: config.mixed_imports,
     })));
   }
   
   if let Some(configs) = &transform_features.change_package_import {
     let change_configs: Vec<Config> = configs.iter().map(|c| match c {
       ChangeConfig::LiteralConfig(s) => Config::LiteralConfig(s.clone()),
+      ChangeConfig::SpecificConfig(c) => Config::SpecificConfig(c.clone()),
+      ChangeConfig::TemplateConfig(c) => Config::TemplateConfig(c.clone()),
     }).collect();
     passes.push(Box::new(change_package_import(change_configs)));
   }
+
+  // Rewrites every import/export into runtime helper calls, so it has to run last
+  if let Some(config) = &transform_features.node_transform {
+    match config {
+      NodeTransformConfig::Bool(true) => {
+        passes.push(Box::new(node_transform(Default::default())));
+      }
+      NodeTransformConfig::Runtime(runtime) => {
+        passes.push(Box::new(node_transform(runtime.clone())));
+      }
+      NodeTransformConfig::Bool(false) => {}
+    }
+  }
   
   ChainedTransform { passes }
 }
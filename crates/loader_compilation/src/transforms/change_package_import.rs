@@ -1,38 +1,187 @@
-use std::collections::HashMap;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
 use swc_core::{
-  common::DUMMY_SP,
+  common::Span,
   ecma::{
     ast::*,
     utils::{quote_str, swc_ecma_ast::ImportSpecifier},
-    visit::{noop_fold_type, Fold, FoldWith, fold_pass},
+    visit::{noop_fold_type, noop_visit_type, Fold, FoldWith, Visit, VisitWith, fold_pass},
   },
 };
 
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum Config {
   LiteralConfig(String),
-  #[allow(dead_code)]
   SpecificConfig(SpecificConfigs),
+  TemplateConfig(TemplateConfig),
+}
+
+/// `{ source, path, style, importType }`, where `path` and `style` are templates such as
+/// `antd/es/{{ kebabCase member }}` rendered for every imported member.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateConfig {
+  pub source: String,
+  pub path: Template,
+  // Side-effect import added after each member, e.g. its stylesheet
+  #[serde(default)]
+  pub style: Option<Template>,
+  // Defaults to a default import of the rendered path
+  #[serde(default)]
+  pub import_type: Option<ImportType>,
+}
+
+/// A path template with `{{ member }}` placeholders, optionally transformed by one of
+/// `kebabCase`, `camelCase`, `snakeCase`, `upperCase` or `lowerCase`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Template {
+  segments: Vec<TemplateSegment>,
 }
 
 #[derive(Debug, Clone)]
+enum TemplateSegment {
+  Literal(String),
+  Member(MemberCase),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MemberCase {
+  Unchanged,
+  Kebab,
+  Camel,
+  Snake,
+  Upper,
+  Lower,
+}
+
+impl TryFrom<String> for Template {
+  type Error = String;
+
+  fn try_from(template: String) -> Result<Self, Self::Error> {
+    let mut segments = vec![];
+    let mut rest = template.as_str();
+    while let Some(start) = rest.find("{{") {
+      if start > 0 {
+        segments.push(TemplateSegment::Literal(rest[..start].to_string()));
+      }
+      let end = rest[start..]
+        .find("}}")
+        .ok_or_else(|| format!("Unclosed placeholder in template '{template}'"))?;
+      let placeholder = rest[start + 2..start + end].split_whitespace().collect::<Vec<_>>();
+      let case = match placeholder.as_slice() {
+        ["member"] => MemberCase::Unchanged,
+        ["kebabCase", "member"] => MemberCase::Kebab,
+        ["camelCase", "member"] => MemberCase::Camel,
+        ["snakeCase", "member"] => MemberCase::Snake,
+        ["upperCase", "member"] => MemberCase::Upper,
+        ["lowerCase", "member"] => MemberCase::Lower,
+        _ => {
+          return Err(format!(
+            "Unknown placeholder '{{{{{}}}}}' in template '{template}'",
+            &rest[start + 2..start + end]
+          ))
+        }
+      };
+      segments.push(TemplateSegment::Member(case));
+      rest = &rest[start + end + 2..];
+    }
+    if !rest.is_empty() {
+      segments.push(TemplateSegment::Literal(rest.to_string()));
+    }
+    Ok(Self { segments })
+  }
+}
+
+impl Template {
+  pub fn render(&self, member: &str) -> String {
+    self
+      .segments
+      .iter()
+      .map(|segment| match segment {
+        TemplateSegment::Literal(literal) => literal.clone(),
+        TemplateSegment::Member(case) => case.apply(member),
+      })
+      .collect()
+  }
+}
+
+impl MemberCase {
+  fn apply(self, member: &str) -> String {
+    match self {
+      MemberCase::Unchanged => member.to_string(),
+      MemberCase::Kebab => split_words(member).join("-"),
+      MemberCase::Snake => split_words(member).join("_"),
+      MemberCase::Camel => split_words(member)
+        .iter()
+        .enumerate()
+        .map(|(index, word)| {
+          let mut chars = word.chars();
+          match chars.next() {
+            Some(first) if index > 0 => first.to_uppercase().chain(chars).collect(),
+            _ => word.clone(),
+          }
+        })
+        .collect(),
+      MemberCase::Upper => member.to_uppercase(),
+      MemberCase::Lower => member.to_lowercase(),
+    }
+  }
+}
+
+// Lowercase words of `DatePicker`, `HTMLElement` or `date_picker`
+fn split_words(name: &str) -> Vec<String> {
+  let chars = name.chars().collect::<Vec<_>>();
+  let mut words = vec![];
+  let mut word = String::new();
+  for (index, &c) in chars.iter().enumerate() {
+    if !c.is_alphanumeric() {
+      if !word.is_empty() {
+        words.push(std::mem::take(&mut word));
+      }
+      continue;
+    }
+    let prev = index.checked_sub(1).map(|prev| chars[prev]);
+    let next = chars.get(index + 1);
+    // `aB` and `1B` start a word, so does the `E` in `HTMLElement`
+    let starts_word = c.is_uppercase()
+      && prev.is_some_and(|prev| {
+        prev.is_lowercase()
+          || prev.is_numeric()
+          || (prev.is_uppercase() && next.is_some_and(|next| next.is_lowercase()))
+      });
+    if starts_word && !word.is_empty() {
+      words.push(std::mem::take(&mut word));
+    }
+    word.extend(c.to_lowercase());
+  }
+  if !word.is_empty() {
+    words.push(word);
+  }
+  words
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct SpecificConfigs {
   pub name: String,
   pub map: HashMap<String, MapProperty>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct MapProperty {
   pub to: String,
+  #[serde(default)]
   pub import_type: Option<ImportType>,
+  #[serde(default)]
   pub name: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum ImportType {
-  #[allow(dead_code)]
   Named,
-  #[allow(dead_code)]
   Default,
 }
 
@@ -61,15 +210,17 @@ impl Fold for ChangePackageImportImpl {
             Config::LiteralConfig(src) => {
               if is_hit_rule(import_decl, option) {
                 hit_rule = true;
+                new_items.extend(create_kept_import_decl(import_decl));
                 for specifier in &import_decl.specifiers {
-                  if let ImportSpecifier::Named(named_import_spec) = specifier {
+                  if let Some(named_import_spec) = rewritten_specifier(specifier) {
                     let mut import_new_src = src.clone();
                     import_new_src.push('/');
                     import_new_src.push_str(&get_import_module_name(named_import_spec));
 
                     new_items.push(create_default_import_decl(
                       import_new_src,
-                      named_import_spec.local.clone(),
+                      named_import_spec,
+                      import_decl,
                     ));
                   }
                 }
@@ -85,12 +236,12 @@ impl Fold for ChangePackageImportImpl {
                 named_import_spec_copy.specifiers = named_import_spec_copy
                   .specifiers
                   .into_iter()
-                  .filter(|specifier| match specifier {
-                    ImportSpecifier::Named(named_import_spec) => {
+                  .filter(|specifier| match rewritten_specifier(specifier) {
+                    Some(named_import_spec) => {
                       let import_object_name = get_import_module_name(named_import_spec);
                       !target_fields.contains(&&import_object_name)
                     }
-                    _ => true,
+                    None => false,
                   })
                   .collect::<Vec<_>>();
 
@@ -98,9 +249,10 @@ impl Fold for ChangePackageImportImpl {
                   new_items.push(item.clone());
                   break;
                 }
+                new_items.extend(create_kept_import_decl(import_decl));
                 for specifier in &import_decl.specifiers {
                   for (target, rules) in config.map.iter() {
-                    if let ImportSpecifier::Named(named_import_spec) = specifier {
+                    if let Some(named_import_spec) = rewritten_specifier(specifier) {
                       let import_object_name = get_import_module_name(named_import_spec);
                       if target == &import_object_name {
                         let new_import_decl: ModuleItem;
@@ -109,7 +261,8 @@ impl Fold for ChangePackageImportImpl {
                         {
                           new_import_decl = create_default_import_decl(
                             rules.to.to_string(),
-                            named_import_spec.local.clone(),
+                            named_import_spec,
+                            import_decl,
                           );
                         } else {
                           let mut named_import_spec_copy = named_import_spec.clone();
@@ -125,6 +278,8 @@ impl Fold for ChangePackageImportImpl {
                           new_import_decl = create_named_import_decl(
                             rules.to.to_string(),
                             vec![ImportSpecifier::Named(named_import_spec_copy)],
+                            named_import_spec.span,
+                            import_decl,
                           );
                         }
 
@@ -136,21 +291,395 @@ impl Fold for ChangePackageImportImpl {
                 break;
               }
             }
+            Config::TemplateConfig(config) => {
+              if is_hit_rule(import_decl, option) {
+                hit_rule = true;
+                new_items.extend(create_kept_import_decl(import_decl));
+                for specifier in &import_decl.specifiers {
+                  if let Some(named_import_spec) = rewritten_specifier(specifier) {
+                    let member = get_import_module_name(named_import_spec);
+                    let path = config.path.render(&member);
+                    new_items.push(match config.import_type {
+                      Some(ImportType::Named) => create_named_import_decl(
+                        path,
+                        vec![ImportSpecifier::Named(named_import_spec.clone())],
+                        named_import_spec.span,
+                        import_decl,
+                      ),
+                      _ => create_default_import_decl(path, named_import_spec, import_decl),
+                    });
+                    if let Some(style) = &config.style {
+                      new_items.push(create_style_import_decl(
+                        style.render(&member),
+                        named_import_spec.span,
+                      ));
+                    }
+                  }
+                }
+                break;
+              }
+            }
           }
         }
 
         if !hit_rule {
           new_items.push(item);
         }
+      } else if let Some(reexports) = self.transform_reexport(&item) {
+        new_items.extend(reexports);
       } else {
         new_items.push(item);
       }
     }
     new_items
   }
+
+  fn fold_expr(&mut self, expr: Expr) -> Expr {
+    let mut expr = expr.fold_children_with(self);
+    match &mut expr {
+      // import('antd').then(m => m.Button)
+      Expr::Call(call) => {
+        if let (Callee::Expr(callee), Some(members)) = (&mut call.callee, then_callback_members(&call.args)) {
+          if let Expr::Member(MemberExpr { obj, prop: MemberProp::Ident(prop), .. }) = &mut **callee {
+            if prop.sym == "then" {
+              self.transform_dynamic_import(obj, &members);
+            }
+          }
+        }
+      }
+      // (await import('antd')).Button
+      Expr::Member(member) => {
+        if let (Expr::Paren(ParenExpr { expr: inner, .. }), Some(member_name)) =
+          (&mut *member.obj, static_member(&member.prop))
+        {
+          if let Expr::Await(AwaitExpr { arg, .. }) = &mut **inner {
+            self.transform_dynamic_import(arg, &BTreeSet::from([member_name]));
+          }
+        }
+      }
+      _ => {}
+    }
+    expr
+  }
+
+  // const { Button } = await import('antd')
+  fn fold_var_declarator(&mut self, decl: VarDeclarator) -> VarDeclarator {
+    let mut decl = decl.fold_children_with(self);
+    if let (Some(init), Some(members)) = (&mut decl.init, object_pat_keys(&decl.name)) {
+      if let Expr::Await(AwaitExpr { arg, .. }) = &mut **init {
+        self.transform_dynamic_import(arg, &members);
+      }
+    }
+    decl
+  }
+}
+
+impl ChangePackageImportImpl {
+  /// Where `member` of `src` is imported from, the first rule matching `src` decides
+  /// like for import declarations.
+  fn import_target(&self, src: &str, member: &str) -> Option<ImportTarget> {
+    self.options.iter().find_map(|option| match option {
+      Config::LiteralConfig(name) if name == src => Some(Some(ImportTarget {
+        to: format!("{name}/{member}"),
+        export: "default".to_string(),
+        style: None,
+      })),
+      Config::SpecificConfig(config) if config.name == src => Some(config.map.get(member).map(|rule| {
+        let export = match rule.import_type {
+          Some(ImportType::Named) => rule.name.clone().unwrap_or_else(|| member.to_string()),
+          _ => "default".to_string(),
+        };
+        ImportTarget {
+          to: rule.to.clone(),
+          export,
+          style: None,
+        }
+      })),
+      Config::TemplateConfig(config) if config.source == src => Some(Some(ImportTarget {
+        to: config.path.render(member),
+        export: match config.import_type {
+          Some(ImportType::Named) => member.to_string(),
+          _ => "default".to_string(),
+        },
+        style: config.style.as_ref().map(|style| style.render(member)),
+      })),
+      _ => None,
+    })?
+  }
+
+  // `export { Button } from 'antd'` becomes `export { default as Button } from 'antd/Button'`
+  fn transform_reexport(&self, item: &ModuleItem) -> Option<Vec<ModuleItem>> {
+    let ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(export)) = item else {
+      return None;
+    };
+    let src = export.src.as_ref()?;
+    if export.type_only {
+      return None;
+    }
+    // Every specifier has to be moved, otherwise the package is still pulled in
+    let reexports = export
+      .specifiers
+      .iter()
+      .map(|specifier| match specifier {
+        ExportSpecifier::Named(named) if !named.is_type_only => {
+          let target = self.import_target(&src.value, named.orig.atom())?;
+          let exported = named.exported.clone().unwrap_or_else(|| named.orig.clone());
+          let mut items = vec![];
+          if let Some(style) = target.style {
+            items.push(create_style_import_decl(style, named.span));
+          }
+          items.push(create_reexport_decl(
+            target.to,
+            &target.export,
+            exported,
+            named.span,
+            export.with.clone(),
+          ));
+          Some(items)
+        }
+        _ => None,
+      })
+      .collect::<Option<Vec<_>>>()?;
+    Some(reexports.into_iter().flatten().collect())
+  }
+
+  // Replaces `import(src)` with imports of the `members` it is read for, resolving to an
+  // object holding just those members
+  fn transform_dynamic_import(&self, expr: &mut Box<Expr>, members: &BTreeSet<String>) {
+    let Some((src, span)) = dynamic_import_src(expr) else {
+      return;
+    };
+    let Some(targets) = members
+      .iter()
+      .map(|member| self.import_target(&src, member).map(|target| (member, target)))
+      .collect::<Option<Vec<_>>>()
+    else {
+      return;
+    };
+    if targets.is_empty() {
+      return;
+    }
+
+    // Promise.all([import(to), ...]).then(m => ({ member: m[0].export, ... })),
+    // with the style imports, if any, loaded after the members
+    let modules = Ident::new_no_ctxt("m".into(), span);
+    let imports = targets
+      .iter()
+      .map(|(_, target)| &target.to)
+      .chain(targets.iter().filter_map(|(_, target)| target.style.as_ref()))
+      .map(|to| {
+        Some(ExprOrSpread {
+          spread: None,
+          expr: Box::new(Expr::Call(CallExpr {
+            span,
+            callee: Callee::Import(Import {
+              span,
+              phase: Default::default(),
+            }),
+            args: vec![Expr::Lit(Lit::Str(quote_str!(span, to.as_str()))).into()],
+            ..Default::default()
+          })),
+        })
+      })
+      .collect();
+    let props = targets
+      .iter()
+      .enumerate()
+      .map(|(index, (member, ImportTarget { export, .. }))| {
+        let value = Expr::Member(MemberExpr {
+          span,
+          obj: Box::new(Expr::Member(MemberExpr {
+            span,
+            obj: Box::new(Expr::Ident(modules.clone())),
+            prop: MemberProp::Computed(ComputedPropName {
+              span,
+              expr: Box::new(Expr::Lit(Lit::Num(Number::from(index)))),
+            }),
+          })),
+          prop: member_prop(export, span),
+        });
+        PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+          key: prop_name(member, span),
+          value: Box::new(value),
+        })))
+      })
+      .collect();
+    let promise_all = Expr::Call(CallExpr {
+      span,
+      callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
+        span,
+        obj: Box::new(Expr::Ident(Ident::new_no_ctxt("Promise".into(), span))),
+        prop: MemberProp::Ident(IdentName::new("all".into(), span)),
+      }))),
+      args: vec![Expr::Array(ArrayLit { span, elems: imports }).into()],
+      ..Default::default()
+    });
+    let callback = Expr::Arrow(ArrowExpr {
+      span,
+      params: vec![Pat::Ident(modules.into())],
+      body: Box::new(BlockStmtOrExpr::Expr(Box::new(Expr::Paren(ParenExpr {
+        span,
+        expr: Box::new(Expr::Object(ObjectLit { span, props })),
+      })))),
+      ..Default::default()
+    });
+    **expr = Expr::Call(CallExpr {
+      span,
+      callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
+        span,
+        obj: Box::new(promise_all),
+        prop: MemberProp::Ident(IdentName::new("then".into(), span)),
+      }))),
+      args: vec![callback.into()],
+      ..Default::default()
+    });
+  }
+}
+
+struct ImportTarget {
+  to: String,
+  export: String,
+  style: Option<String>,
+}
+
+// The source of `import('antd')`
+fn dynamic_import_src(expr: &Expr) -> Option<(String, Span)> {
+  let Expr::Call(CallExpr { callee: Callee::Import(_), args, span, .. }) = expr else {
+    return None;
+  };
+  match args.as_slice() {
+    [ExprOrSpread { spread: None, expr }] => match &**expr {
+      Expr::Lit(Lit::Str(str)) => Some((str.value.to_string(), *span)),
+      Expr::Tpl(tpl) if tpl.exprs.is_empty() => {
+        Some((tpl.quasis.first()?.cooked.as_ref()?.to_string(), *span))
+      }
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+// Members read from the module passed to a `.then()` callback
+fn then_callback_members(args: &[ExprOrSpread]) -> Option<BTreeSet<String>> {
+  let callback = args.first()?;
+  if callback.spread.is_some() {
+    return None;
+  }
+  let (param, body): (&Pat, &dyn VisitWith<MemberCollector>) = match &*callback.expr {
+    Expr::Arrow(arrow) => (arrow.params.first()?, &*arrow.body),
+    Expr::Fn(fn_expr) => (
+      &fn_expr.function.params.first()?.pat,
+      fn_expr.function.body.as_ref()?,
+    ),
+    _ => return None,
+  };
+  match param {
+    Pat::Ident(binding) => {
+      let mut collector = MemberCollector {
+        id: binding.id.to_id(),
+        members: Some(BTreeSet::new()),
+      };
+      body.visit_with(&mut collector);
+      collector.members
+    }
+    pat => object_pat_keys(pat),
+  }
+}
+
+// Keys of `{ Button, Input: I }`, as long as there is no rest element
+fn object_pat_keys(pat: &Pat) -> Option<BTreeSet<String>> {
+  let Pat::Object(object) = pat else {
+    return None;
+  };
+  object
+    .props
+    .iter()
+    .map(|prop| match prop {
+      ObjectPatProp::Assign(assign) => Some(assign.key.sym.to_string()),
+      ObjectPatProp::KeyValue(KeyValuePatProp { key: PropName::Ident(ident), .. }) => {
+        Some(ident.sym.to_string())
+      }
+      ObjectPatProp::KeyValue(KeyValuePatProp { key: PropName::Str(str), .. }) => {
+        Some(str.value.to_string())
+      }
+      _ => None,
+    })
+    .collect()
+}
+
+fn static_member(prop: &MemberProp) -> Option<String> {
+  match prop {
+    MemberProp::Ident(ident) => Some(ident.sym.to_string()),
+    MemberProp::Computed(ComputedPropName { expr, .. }) => match &**expr {
+      Expr::Lit(Lit::Str(str)) => Some(str.value.to_string()),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+// Reserved words are fine as property and export names
+fn is_identifier_name(name: &str) -> bool {
+  let mut chars = name.chars();
+  chars.next().is_some_and(Ident::is_valid_start) && chars.all(Ident::is_valid_continue)
+}
+
+fn member_prop(name: &str, span: Span) -> MemberProp {
+  if is_identifier_name(name) {
+    MemberProp::Ident(IdentName::new(name.into(), span))
+  } else {
+    MemberProp::Computed(ComputedPropName {
+      span,
+      expr: Box::new(Expr::Lit(Lit::Str(quote_str!(span, name)))),
+    })
+  }
+}
+
+fn prop_name(name: &str, span: Span) -> PropName {
+  if is_identifier_name(name) {
+    PropName::Ident(IdentName::new(name.into(), span))
+  } else {
+    PropName::Str(quote_str!(span, name))
+  }
+}
+
+// Static members read from `id`, `None` once it is used any other way
+struct MemberCollector {
+  id: Id,
+  members: Option<BTreeSet<String>>,
+}
+
+impl Visit for MemberCollector {
+  noop_visit_type!();
+
+  fn visit_ident(&mut self, ident: &Ident) {
+    if ident.to_id() == self.id {
+      self.members = None;
+    }
+  }
+
+  fn visit_member_expr(&mut self, member: &MemberExpr) {
+    if let Expr::Ident(obj) = &*member.obj {
+      if obj.to_id() == self.id {
+        match (self.members.as_mut(), static_member(&member.prop)) {
+          (Some(members), Some(name)) => {
+            members.insert(name);
+          }
+          _ => self.members = None,
+        }
+        member.prop.visit_with(self);
+        return;
+      }
+    }
+    member.visit_children_with(self);
+  }
 }
 
 fn is_hit_rule(cur_import: &ImportDecl, rule: &Config) -> bool {
+  // A type has no module of its own, so `import type` stays on the package
+  if cur_import.type_only {
+    return false;
+  }
   match rule {
     Config::LiteralConfig(s) => {
       if cur_import.src.value == s.clone() {
@@ -164,6 +693,7 @@ fn is_hit_rule(cur_import: &ImportDecl, rule: &Config) -> bool {
       }
       false
     }
+    Config::TemplateConfig(s) => cur_import.src.value == s.source,
   }
 }
 
@@ -178,31 +708,104 @@ fn get_import_module_name(named_import_spec: &ImportNamedSpecifier) -> String {
   }
 }
 
-fn create_default_import_decl(src: String, local: Ident) -> ModuleItem {
+// Generated imports take the span of the specifier they replace, and the attributes of the
+// import they come from.
+fn create_default_import_decl(
+  src: String,
+  specifier: &ImportNamedSpecifier,
+  origin: &ImportDecl,
+) -> ModuleItem {
+  let span = specifier.span;
   wrap_with_moudle_item(ImportDecl {
     phase: Default::default(),
-    src: Box::new(quote_str!(src)),
+    src: Box::new(quote_str!(span, src)),
     specifiers: vec![ImportSpecifier::Default(ImportDefaultSpecifier {
-      span: DUMMY_SP,
-      local,
+      span,
+      local: specifier.local.clone(),
     })],
-    span: DUMMY_SP,
+    span,
     type_only: false,
-    with: None,
+    with: origin.with.clone(),
   })
 }
 
-fn create_named_import_decl(src: String, specifiers: Vec<ImportSpecifier>) -> ModuleItem {
+fn create_reexport_decl(
+  src: String,
+  export: &str,
+  exported: ModuleExportName,
+  span: Span,
+  with: Option<Box<ObjectLit>>,
+) -> ModuleItem {
+  let orig = if is_identifier_name(export) {
+    ModuleExportName::Ident(Ident::new_no_ctxt(export.into(), span))
+  } else {
+    ModuleExportName::Str(quote_str!(span, export))
+  };
+  let exported = (exported.atom() != export).then_some(exported);
+  ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(NamedExport {
+    span,
+    specifiers: vec![ExportSpecifier::Named(ExportNamedSpecifier {
+      span,
+      orig,
+      exported,
+      is_type_only: false,
+    })],
+    src: Some(Box::new(quote_str!(span, src))),
+    type_only: false,
+    with,
+  }))
+}
+
+fn create_named_import_decl(
+  src: String,
+  specifiers: Vec<ImportSpecifier>,
+  span: Span,
+  origin: &ImportDecl,
+) -> ModuleItem {
   wrap_with_moudle_item(ImportDecl {
     phase: Default::default(),
-    src: Box::new(quote_str!(src)),
+    src: Box::new(quote_str!(span, src)),
     specifiers,
-    span: DUMMY_SP,
+    span,
+    type_only: false,
+    with: origin.with.clone(),
+  })
+}
+
+// Side-effect import of a member's style, only added for runtime imports
+fn create_style_import_decl(src: String, span: Span) -> ModuleItem {
+  wrap_with_moudle_item(ImportDecl {
+    phase: Default::default(),
+    src: Box::new(quote_str!(span, src)),
+    specifiers: vec![],
+    span,
     type_only: false,
     with: None,
   })
 }
 
+// A named specifier that moves to its member's module. `import { type X }` stays on the
+// package, as a type has no module of its own.
+fn rewritten_specifier(specifier: &ImportSpecifier) -> Option<&ImportNamedSpecifier> {
+  match specifier {
+    ImportSpecifier::Named(named) if !named.is_type_only => Some(named),
+    _ => None,
+  }
+}
+
+// The import with only its default, namespace and type-only specifiers, which still need
+// the package. A side-effect import has nothing to rewrite and is kept as written.
+fn create_kept_import_decl(import_decl: &ImportDecl) -> Option<ModuleItem> {
+  if import_decl.specifiers.is_empty() {
+    return Some(wrap_with_moudle_item(import_decl.clone()));
+  }
+  let mut kept = import_decl.clone();
+  kept
+    .specifiers
+    .retain(|specifier| rewritten_specifier(specifier).is_none());
+  (!kept.specifiers.is_empty()).then(|| wrap_with_moudle_item(kept))
+}
+
 fn wrap_with_moudle_item(import_decl: ImportDecl) -> ModuleItem {
   ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl))
 }
@@ -217,7 +820,8 @@ mod tests {
   use swc_core::{
     common::{FileName, SourceMap},
     ecma::{
-      parser::{lexer::Lexer, Parser, StringInput, Syntax},
+      codegen::{text_writer::JsWriter, Emitter},
+      parser::{lexer::Lexer, Parser, StringInput, Syntax, TsSyntax},
       visit::FoldWith,
     },
   };
@@ -235,6 +839,60 @@ mod tests {
     parser.parse_module().expect("Failed to parse module")
   }
 
+  fn parse_ts(code: &str) -> Module {
+    let cm = SourceMap::default();
+    let fm = cm.new_source_file(FileName::Anon.into(), code.to_string());
+    let lexer = Lexer::new(
+      Syntax::Typescript(TsSyntax::default()),
+      Default::default(),
+      StringInput::from(&*fm),
+      None,
+    );
+    let mut parser = Parser::new_from(lexer);
+    parser.parse_module().expect("Failed to parse module")
+  }
+
+  fn emit_js(module: &Module) -> String {
+    let mut buf = vec![];
+    {
+      let writer = JsWriter::new(SourceMap::default().into(), "\n", &mut buf, None);
+      let mut emitter = Emitter {
+        cfg: Default::default(),
+        comments: None,
+        cm: SourceMap::default().into(),
+        wr: writer,
+      };
+      emitter.emit_module(module).unwrap();
+    }
+    String::from_utf8(buf).unwrap()
+  }
+
+  fn ice_config() -> Config {
+    let mut map = HashMap::new();
+    map.insert("a".to_string(), MapProperty {
+      to: "@ice/x/a".to_string(),
+      import_type: None,
+      name: None,
+    });
+    map.insert("b".to_string(), MapProperty {
+      to: "@ice/x/b".to_string(),
+      import_type: Some(ImportType::Named),
+      name: Some("newB".to_string()),
+    });
+    Config::SpecificConfig(SpecificConfigs {
+      name: "ice".to_string(),
+      map,
+    })
+  }
+
+  fn transform_code(code: &str) -> String {
+    let module = parse_js(code).fold_with(&mut ChangePackageImportImpl::new(vec![
+      Config::LiteralConfig("antd".to_string()),
+      ice_config(),
+    ]));
+    emit_js(&module)
+  }
+
   #[test]
   fn test_literal_config_transform() {
     let code = r#"import { Button, Input } from 'antd';"#;
@@ -345,4 +1003,207 @@ mod tests {
     // Should only transform named imports, keep default imports
     assert!(!module.body.is_empty());
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_reexport_transform() {
+    let output = transform_code(
+      "export { Button, Input as I } from 'antd';\nexport { a, b as c } from 'ice';",
+    );
+    assert_eq!(
+      output,
+      r#"export { default as Button } from "antd/Button";
+export { default as I } from "antd/Input";
+export { default as a } from "@ice/x/a";
+export { newB as c } from "@ice/x/b";
+"#
+    );
+  }
+
+  #[test]
+  fn test_reexport_partially_mapped() {
+    // `other` is not in the map, so the package is still needed and nothing is moved
+    let output = transform_code("export { a, other } from 'ice';\nexport * from 'antd';");
+    assert_eq!(output, "export { a, other } from 'ice';\nexport * from 'antd';\n");
+  }
+
+  #[test]
+  fn test_dynamic_import_transform() {
+    let output = transform_code(
+      r#"import('antd').then(m => m.Button);
+import('ice').then(function({ a, b }) { return [a, b]; });
+const { Input } = await import('antd');
+const Form = (await import('antd')).Form;"#,
+    );
+    let output = output.split_whitespace().collect::<String>();
+    for expected in [
+      r#"Promise.all([import("antd/Button")]).then((m)=>({Button:m[0].default})).then((m)=>m.Button);"#,
+      r#"Promise.all([import("@ice/x/a"),import("@ice/x/b")]).then((m)=>({a:m[0].default,b:m[1].newB})).then(function({a,b}){"#,
+      r#"const{Input}=awaitPromise.all([import("antd/Input")]).then((m)=>({Input:m[0].default}));"#,
+      r#"constForm=(awaitPromise.all([import("antd/Form")]).then((m)=>({Form:m[0].default}))).Form;"#,
+    ] {
+      assert!(output.contains(expected), "{output}");
+    }
+  }
+
+  #[test]
+  fn test_dynamic_import_not_static() {
+    for code in [
+      "import('antd').then(m => m[key]);",
+      "import('antd').then(m => console.log(m));",
+      "import('ice').then(({ a, other }) => a);",
+      "const { Button, ...rest } = await import('antd');",
+      "const antd = await import('antd');",
+    ] {
+      let output = transform_code(code);
+      assert!(!output.contains("Promise.all"), "{output}");
+    }
+  }
+
+  fn template_config(import_type: Option<ImportType>) -> Config {
+    Config::TemplateConfig(TemplateConfig {
+      source: "antd".to_string(),
+      path: Template::try_from("antd/es/{{ kebabCase member }}".to_string()).unwrap(),
+      style: Some(Template::try_from("antd/es/{{kebabCase member}}/style".to_string()).unwrap()),
+      import_type,
+    })
+  }
+
+  #[test]
+  fn test_template_render() {
+    let render = |template: &str, member: &str| {
+      Template::try_from(template.to_string()).unwrap().render(member)
+    };
+    assert_eq!(render("antd/es/{{ kebabCase member }}", "DatePicker"), "antd/es/date-picker");
+    assert_eq!(render("lib/{{ snakeCase member }}.js", "HTMLElement2D"), "lib/html_element2_d.js");
+    assert_eq!(render("{{ camelCase member }}", "TreeSelect"), "treeSelect");
+    assert_eq!(render("{{member}}/{{ lowerCase member }}", "QRCode"), "QRCode/qrcode");
+    assert_eq!(render("{{ upperCase member }}", "Button"), "BUTTON");
+
+    assert!(Template::try_from("antd/{{ dashCase member }}".to_string()).is_err());
+    assert!(Template::try_from("antd/{{ member".to_string()).is_err());
+  }
+
+  #[test]
+  fn test_template_config_transform() {
+    let module = parse_js("import Antd, { DatePicker, Button as B } from 'antd';")
+      .fold_with(&mut ChangePackageImportImpl::new(vec![template_config(None)]));
+    assert_eq!(
+      emit_js(&module),
+      r#"import Antd from 'antd';
+import DatePicker from "antd/es/date-picker";
+import "antd/es/date-picker/style";
+import B from "antd/es/button";
+import "antd/es/button/style";
+"#
+    );
+
+    let module = parse_js("import { DatePicker } from 'antd';").fold_with(
+      &mut ChangePackageImportImpl::new(vec![template_config(Some(ImportType::Named))]),
+    );
+    assert!(
+      emit_js(&module).starts_with(r#"import { DatePicker } from "antd/es/date-picker";"#)
+    );
+  }
+
+  #[test]
+  fn test_template_config_reexport_and_dynamic_import() {
+    let module = parse_js(
+      "export { DatePicker as Picker } from 'antd';\nimport('antd').then(({ Button }) => Button);",
+    )
+    .fold_with(&mut ChangePackageImportImpl::new(vec![template_config(None)]));
+    let output = emit_js(&module);
+    assert!(
+      output.starts_with(
+        r#"import "antd/es/date-picker/style";
+export { default as Picker } from "antd/es/date-picker";"#
+      ),
+      "{output}"
+    );
+    assert!(
+      output.split_whitespace().collect::<String>().contains(
+        r#"Promise.all([import("antd/es/button"),import("antd/es/button/style")]).then((m)=>({Button:m[0].default}))"#
+      ),
+      "{output}"
+    );
+  }
+
+  #[test]
+  fn test_literal_config_keeps_default_import() {
+    let output = transform_code("import Antd, { Button } from 'antd';");
+    assert_eq!(output, "import Antd from 'antd';\nimport Button from \"antd/Button\";\n");
+
+    let output = transform_code("import * as antd from 'antd';");
+    assert_eq!(output, "import * as antd from 'antd';\n");
+  }
+
+  #[test]
+  fn test_side_effect_import_is_kept() {
+    assert_eq!(transform_code("import 'antd';"), "import 'antd';\n");
+    assert_eq!(transform_code("import {} from 'antd';"), "import 'antd';\n");
+    assert_eq!(transform_code("import 'ice';"), "import 'ice';\n");
+
+    let module = parse_js("import 'antd';")
+      .fold_with(&mut ChangePackageImportImpl::new(vec![template_config(None)]));
+    assert_eq!(emit_js(&module), "import 'antd';\n");
+  }
+
+  #[test]
+  fn test_specific_config_keeps_default_import() {
+    let output = transform_code("import Ice, { a } from 'ice';");
+    assert_eq!(output, "import Ice from 'ice';\nimport a from \"@ice/x/a\";\n");
+  }
+
+  #[test]
+  fn test_type_only_imports() {
+    let module = parse_ts(
+      "import type { ButtonProps } from 'antd';\nimport { type InputProps, Input } from 'antd';",
+    )
+    .fold_with(&mut ChangePackageImportImpl::new(vec![template_config(None)]));
+    assert_eq!(
+      emit_js(&module),
+      r#"import type { ButtonProps } from 'antd';
+import { type InputProps } from 'antd';
+import Input from "antd/es/input";
+import "antd/es/input/style";
+"#
+    );
+
+    let module = parse_ts("import { type ButtonProps } from 'antd';").fold_with(
+      &mut ChangePackageImportImpl::new(vec![template_config(Some(ImportType::Named))]),
+    );
+    assert_eq!(emit_js(&module), "import { type ButtonProps } from 'antd';\n");
+
+    let module = parse_ts("import { type a, b } from 'ice';\nimport { type c } from 'ice';")
+      .fold_with(&mut ChangePackageImportImpl::new(vec![
+        Config::LiteralConfig("ice".to_string()),
+      ]));
+    assert_eq!(
+      emit_js(&module),
+      "import { type a } from 'ice';\nimport b from \"ice/b\";\nimport { type c } from 'ice';\n"
+    );
+  }
+
+  #[test]
+  fn test_import_attributes() {
+    let module = parse_ts(
+      "import { a, b } from 'ice' with { type: 'json' };\nexport { Button } from 'antd' with { type: 'json' };",
+    );
+    let output = emit_js(&module.fold_with(&mut ChangePackageImportImpl::new(vec![
+      Config::LiteralConfig("antd".to_string()),
+      ice_config(),
+    ])));
+    assert_eq!(
+      output,
+      r#"import a from "@ice/x/a" with {
+    type: 'json'
+};
+import { newB as b } from "@ice/x/b" with {
+    type: 'json'
+};
+export { default as Button } from "antd/Button" with {
+    type: 'json'
+};
+"#
+    );
+  }
+}
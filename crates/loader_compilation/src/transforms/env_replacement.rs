@@ -1,40 +1,52 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::Deserialize;
 use swc_core::{
-  common::{SyntaxContext, DUMMY_SP},
+  common::{Span, Spanned, SyntaxContext},
   ecma::{
     ast::*,
-    visit::{Fold, FoldWith, fold_pass},
+    atoms::Atom,
+    utils::find_pat_ids,
+    visit::{fold_pass, Fold, FoldWith, Visit, VisitWith},
   },
 };
 
+use crate::transforms::keep_export::exported_name;
+
 struct EnvReplacementImpl {
   sources: Vec<String>,
+  flags: EnvFlags,
+  // Bindings of flags folded to a literal
+  constants: HashMap<Id, bool>,
+  // Bindings of flag objects from default/namespace imports and `require`
+  namespaces: HashSet<Id>,
 }
 
-fn create_check_expr(meta_value: &str, renderer: &str) -> Expr {
+fn create_check_expr(meta_value: &str, renderer: &str, span: Span) -> Expr {
   Expr::Bin(BinExpr {
-    span: DUMMY_SP,
+    span,
     op: BinaryOp::EqEqEq,
     left: Box::new(Expr::Member(MemberExpr {
-      span: DUMMY_SP,
+      span,
       obj: Box::new(Expr::MetaProp(MetaPropExpr {
-        span: DUMMY_SP,
+        span,
         kind: MetaPropKind::ImportMeta,
       })),
-      prop: MemberProp::Ident(Ident::new(meta_value.into(), DUMMY_SP, SyntaxContext::empty()).into()),
+      prop: MemberProp::Ident(Ident::new(meta_value.into(), span, SyntaxContext::empty()).into()),
     })),
     right: Box::new(Expr::Lit(Lit::Str(Str {
       value: renderer.into(),
-      span: DUMMY_SP,
+      span,
       raw: None,
     }))),
   })
 }
 
-fn create_typeof_check(expr: Expr, check_value: &str, op: BinaryOp) -> Expr {
+fn create_typeof_check(expr: Expr, check_value: &str, op: BinaryOp, span: Span) -> Expr {
   let typeof_expr = Expr::Unary(UnaryExpr {
     op: UnaryOp::TypeOf,
     arg: Box::new(expr),
-    span: DUMMY_SP,
+    span,
   });
 
   Expr::Bin(BinExpr {
@@ -42,18 +54,18 @@ fn create_typeof_check(expr: Expr, check_value: &str, op: BinaryOp) -> Expr {
     op,
     right: Box::new(Expr::Lit(Lit::Str(Str {
       value: check_value.into(),
-      span: DUMMY_SP,
+      span,
       raw: None,
     }))),
-    span: DUMMY_SP,
+    span,
   })
 }
 
-fn combine_check_exprs(exprs: Vec<Expr>, op: BinaryOp) -> Expr {
+fn combine_check_exprs(exprs: Vec<Expr>, op: BinaryOp, span: Span) -> Expr {
   let mut result = exprs[0].clone();
   for expr in exprs[1..].iter() {
     result = Expr::Bin(BinExpr {
-      span: DUMMY_SP,
+      span,
       op,
       left: Box::new(result),
       right: Box::new(expr.clone()),
@@ -62,138 +74,280 @@ fn combine_check_exprs(exprs: Vec<Expr>, op: BinaryOp) -> Expr {
   result
 }
 
-fn build_regex_test_expression() -> Expr {
+fn build_regex_test_expression(span: Span) -> Expr {
   let regex_pattern = Expr::Lit(Lit::Regex(Regex {
     exp: ".+AliApp\\\\((\\\\w+)\\\\/((?:\\\\d+\\\\.)+\\\\d+)\\\\).* .*(WindVane)(?:\\\\/((?:\\\\d+\\\\.)+\\\\d+))?.*"
       .into(),
     flags: "".into(),
-    span: DUMMY_SP,
+    span,
   }));
 
   let typeof_navigator = Expr::Unary(UnaryExpr {
     op: UnaryOp::TypeOf,
-    arg: Box::new(Expr::Ident(Ident::new("navigator".into(), DUMMY_SP, SyntaxContext::empty()))),
-    span: DUMMY_SP,
+    arg: Box::new(Expr::Ident(Ident::new("navigator".into(), span, SyntaxContext::empty()))),
+    span,
   });
 
   let conditional = Expr::Cond(CondExpr {
     test: Box::new(typeof_navigator),
     cons: Box::new(Expr::Bin(BinExpr {
       left: Box::new(Expr::Member(MemberExpr {
-        obj: Box::new(Expr::Ident(Ident::new("navigator".into(), DUMMY_SP, SyntaxContext::empty()))),
-        prop: MemberProp::Ident(Ident::new("userAgent".into(), DUMMY_SP, SyntaxContext::empty()).into()),
-        span: DUMMY_SP,
+        obj: Box::new(Expr::Ident(Ident::new("navigator".into(), span, SyntaxContext::empty()))),
+        prop: MemberProp::Ident(Ident::new("userAgent".into(), span, SyntaxContext::empty()).into()),
+        span,
       })),
       op: BinaryOp::LogicalOr,
       right: Box::new(Expr::Member(MemberExpr {
-        obj: Box::new(Expr::Ident(Ident::new("navigator".into(), DUMMY_SP, SyntaxContext::empty()))),
-        prop: MemberProp::Ident(Ident::new("swuserAgent".into(), DUMMY_SP, SyntaxContext::empty()).into()),
-        span: DUMMY_SP,
+        obj: Box::new(Expr::Ident(Ident::new("navigator".into(), span, SyntaxContext::empty()))),
+        prop: MemberProp::Ident(Ident::new("swuserAgent".into(), span, SyntaxContext::empty()).into()),
+        span,
       })),
-      span: DUMMY_SP,
+      span,
     })),
     alt: Box::new(Expr::Lit(Lit::Str(Str {
       value: "".into(),
-      span: DUMMY_SP,
+      span,
       raw: None,
     }))),
-    span: DUMMY_SP,
+    span,
   });
 
   Expr::Call(CallExpr {
     callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
       obj: Box::new(regex_pattern),
-      prop: MemberProp::Ident(Ident::new("test".into(), DUMMY_SP, SyntaxContext::empty()).into()),
-      span: DUMMY_SP,
+      prop: MemberProp::Ident(Ident::new("test".into(), span, SyntaxContext::empty()).into()),
+      span,
     }))),
     args: vec![ExprOrSpread {
       spread: None,
       expr: Box::new(conditional),
     }],
-    span: DUMMY_SP,
+    span,
     type_args: None,
     ctxt: Default::default(),
   })
 }
 
-fn get_env_expr(specifier: &Ident) -> Expr {
-  match specifier.sym.as_ref() {
-    "isClient" => create_check_expr("renderer", "client"),
-    "isServer" => create_check_expr("renderer", "server"),
-    "isWeb" => combine_check_exprs(
-      vec![
-        create_check_expr("renderer", "client"),
-        create_check_expr("target", "web"),
-      ],
-      BinaryOp::LogicalAnd,
-    ),
-    "isNode" => create_check_expr("renderer", "server"),
-    "isWeex" => combine_check_exprs(
-      vec![
-        create_check_expr("renderer", "client"),
-        create_check_expr("target", "weex"),
-      ],
-      BinaryOp::LogicalAnd,
-    ),
-    "isKraken" => combine_check_exprs(
-      vec![
-        create_check_expr("renderer", "client"),
-        create_check_expr("target", "kraken"),
-      ],
-      BinaryOp::LogicalAnd,
-    ),
-    "isPHA" => combine_check_exprs(
-      vec![
-        create_check_expr("renderer", "client"),
-        create_check_expr("target", "web"),
-        create_typeof_check(
-          Expr::Ident(Ident::new("pha".into(), DUMMY_SP, SyntaxContext::empty())),
-          "object",
-          BinaryOp::EqEqEq,
-        ),
-      ],
-      BinaryOp::LogicalAnd,
+/// Conditions of a user-defined env flag, all of which must hold for the flag to be true.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EnvFlagCondition {
+  /// Expected `import.meta.renderer`, e.g. "client"
+  pub renderer: Option<String>,
+  /// Expected `import.meta.target`, e.g. "web"
+  pub target: Option<String>,
+  /// Globals that must be defined at runtime, e.g. "pha" or "WindVane.call"
+  pub globals: Vec<String>,
+}
+
+/// Either the sources to replace, using the built-in flags, or the sources along with
+/// extra flags. Flags sharing a name with a built-in one replace it.
+///
+/// When the compilation's `renderer` and/or `target` are declared, flags are folded to
+/// `true`/`false` literals and the `if` branches they rule out are dropped.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum EnvReplacementConfig {
+  Sources(Vec<String>),
+  Options {
+    sources: Vec<String>,
+    #[serde(default)]
+    flags: BTreeMap<String, EnvFlagCondition>,
+    #[serde(default)]
+    renderer: Option<String>,
+    #[serde(default)]
+    target: Option<String>,
+  },
+}
+
+#[derive(Debug, Clone)]
+enum EnvCheck {
+  // `import.meta[key] === value`
+  Meta(&'static str, String),
+  // `typeof global op value`
+  TypeOf(String, BinaryOp, &'static str),
+  // The WindVane container user agent test
+  WindVaneUserAgent,
+}
+
+impl EnvCheck {
+  fn to_expr(&self, span: Span) -> Expr {
+    match self {
+      EnvCheck::Meta(key, value) => create_check_expr(key, value, span),
+      EnvCheck::TypeOf(global, op, value) => {
+        create_typeof_check(create_global_expr(global, span), value, *op, span)
+      }
+      EnvCheck::WindVaneUserAgent => build_regex_test_expression(span),
+    }
+  }
+}
+
+struct EnvFlags {
+  // In default export order, `None` flags are always false
+  flags: Vec<(String, Option<Vec<EnvCheck>>)>,
+  // `import.meta.renderer` and `import.meta.target` of the compilation, when known
+  renderer: Option<String>,
+  target: Option<String>,
+}
+
+impl EnvFlags {
+  // Every flag exported by name, `isServer` is not part of the default export
+  fn names(&self) -> impl Iterator<Item = &str> {
+    let is_server = (!self.flags.iter().any(|(flag, _)| flag == "isServer")).then_some("isServer");
+    self.flags.iter().map(|(flag, _)| flag.as_str()).chain(is_server)
+  }
+
+  fn is_flag(&self, name: &str) -> bool {
+    self.names().any(|flag| flag == name)
+  }
+
+  // Statically known result of a check
+  fn evaluate(&self, check: &EnvCheck) -> Option<bool> {
+    match check {
+      EnvCheck::Meta("renderer", value) => self.renderer.as_ref().map(|r| r == value),
+      EnvCheck::Meta("target", value) => self.target.as_ref().map(|t| t == value),
+      _ => None,
+    }
+  }
+}
+
+fn renderer_check(value: &str) -> EnvCheck {
+  EnvCheck::Meta("renderer", value.to_string())
+}
+
+fn target_check(value: &str) -> EnvCheck {
+  EnvCheck::Meta("target", value.to_string())
+}
+
+fn get_builtin_flags() -> Vec<(String, Option<Vec<EnvCheck>>)> {
+  let defined = |global: &str| EnvCheck::TypeOf(global.to_string(), BinaryOp::NotEqEq, "undefined");
+  vec![
+    ("isWeb", Some(vec![renderer_check("client"), target_check("web")])),
+    ("isClient", Some(vec![renderer_check("client")])),
+    ("isNode", Some(vec![renderer_check("server")])),
+    ("isWeex", Some(vec![renderer_check("client"), target_check("weex")])),
+    ("isKraken", Some(vec![renderer_check("client"), target_check("kraken")])),
+    ("isMiniApp", None),
+    ("isByteDanceMicroApp", None),
+    ("isBaiduSmartProgram", None),
+    ("isKuaiShouMiniProgram", None),
+    ("isWeChatMiniProgram", None),
+    ("isQuickApp", None),
+    (
+      "isPHA",
+      Some(vec![
+        renderer_check("client"),
+        target_check("web"),
+        EnvCheck::TypeOf("pha".to_string(), BinaryOp::EqEqEq, "object"),
+      ]),
     ),
-    "isWindVane" => combine_check_exprs(
-      vec![
-        create_check_expr("renderer", "client"),
-        build_regex_test_expression(),
-        create_typeof_check(
-          Expr::Ident(Ident::new("WindVane".into(), DUMMY_SP, SyntaxContext::empty())),
-          "undefined",
-          BinaryOp::NotEqEq,
-        ),
-        create_typeof_check(
-          Expr::Member(MemberExpr {
-            span: DUMMY_SP,
-            obj: Box::new(Expr::Ident(Ident::new("WindVane".into(), DUMMY_SP, SyntaxContext::empty()))),
-            prop: MemberProp::Ident(Ident::new("call".into(), DUMMY_SP, SyntaxContext::empty()).into()),
-          }),
-          "undefined",
-          BinaryOp::NotEqEq,
-        ),
-      ],
-      BinaryOp::LogicalAnd,
+    (
+      "isWindVane",
+      Some(vec![
+        renderer_check("client"),
+        EnvCheck::WindVaneUserAgent,
+        defined("WindVane"),
+        defined("WindVane.call"),
+      ]),
     ),
-    _ => {
-      Expr::Lit(Lit::Bool(Bool {
-        span: DUMMY_SP,
-        value: false,
-      }))
+    ("isFRM", None),
+  ]
+  .into_iter()
+  .map(|(name, checks)| (name.to_string(), checks))
+  .collect()
+}
+
+fn get_env_flags(
+  flags: BTreeMap<String, EnvFlagCondition>,
+  renderer: Option<String>,
+  target: Option<String>,
+) -> EnvFlags {
+  let mut env_flags = get_builtin_flags();
+  for (name, condition) in flags {
+    let checks = condition
+      .renderer
+      .iter()
+      .map(|value| renderer_check(value))
+      .chain(condition.target.iter().map(|value| target_check(value)))
+      .chain(
+        condition
+          .globals
+          .into_iter()
+          .map(|global| EnvCheck::TypeOf(global, BinaryOp::NotEqEq, "undefined")),
+      )
+      .collect();
+    match env_flags.iter_mut().find(|(flag, _)| *flag == name) {
+      Some((_, builtin)) => *builtin = Some(checks),
+      None => env_flags.push((name, Some(checks))),
+    }
+  }
+  EnvFlags {
+    flags: env_flags,
+    renderer,
+    target,
+  }
+}
+
+// `a.b.c` as a member expression
+fn create_global_expr(global: &str, span: Span) -> Expr {
+  let mut parts = global.split('.');
+  let root = Expr::Ident(Ident::new(
+    parts.next().unwrap_or_default().into(),
+    span,
+    SyntaxContext::empty(),
+  ));
+  parts.fold(root, |obj, prop| {
+    Expr::Member(MemberExpr {
+      span,
+      obj: Box::new(obj),
+      prop: MemberProp::Ident(IdentName::new(prop.into(), span)),
+    })
+  })
+}
+
+fn get_env_expr(flags: &EnvFlags, specifier: &Ident, span: Span) -> Expr {
+  let name = specifier.sym.as_ref();
+  let checks = match flags.flags.iter().find(|(flag, _)| flag == name) {
+    Some((_, checks)) => checks.clone(),
+    // Not listed in the default export
+    None if name == "isServer" => Some(vec![renderer_check("server")]),
+    None => None,
+  };
+  let Some(checks) = checks else {
+    return create_bool_expr(false, span);
+  };
+  let mut exprs = vec![];
+  for check in &checks {
+    match flags.evaluate(check) {
+      Some(true) => {}
+      Some(false) => return create_bool_expr(false, span),
+      None => exprs.push(check.to_expr(span)),
     }
   }
+  if exprs.is_empty() {
+    create_bool_expr(true, span)
+  } else {
+    combine_check_exprs(exprs, BinaryOp::LogicalAnd, span)
+  }
+}
+
+fn create_bool_expr(value: bool, span: Span) -> Expr {
+  Expr::Lit(Lit::Bool(Bool {
+    span,
+    value,
+  }))
 }
 
-fn create_env_declare(specifier: &Ident, imported: &Ident) -> Stmt {
-  let expr = get_env_expr(specifier);
+fn create_env_declare(flags: &EnvFlags, specifier: &Ident, imported: &Ident, span: Span) -> Stmt {
+  let expr = get_env_expr(flags, specifier, span);
 
   Stmt::Decl(Decl::Var(Box::new(VarDecl {
-    span: DUMMY_SP,
+    span,
     kind: VarDeclKind::Var,
     declare: false,
     ctxt: Default::default(),
     decls: vec![VarDeclarator {
-      span: DUMMY_SP,
+      span,
       name: Pat::Ident(BindingIdent {
         id: imported.clone(),
         type_ann: Default::default(),
@@ -204,51 +358,115 @@ fn create_env_declare(specifier: &Ident, imported: &Ident) -> Stmt {
   })))
 }
 
-fn create_env_default_export(export_name: Ident) -> Stmt {
+fn create_env_default_export(flags: &EnvFlags, export_name: Ident, span: Span) -> Stmt {
   Stmt::Decl(Decl::Var(Box::new(VarDecl {
     ctxt: Default::default(),
-    span: DUMMY_SP,
+    span,
     kind: VarDeclKind::Const,
     declare: false,
     decls: vec![VarDeclarator {
-      span: DUMMY_SP,
+      span,
       name: Pat::Ident(BindingIdent {
         id: export_name.clone(),
         type_ann: Default::default(),
       }),
       init: Some(Box::new(Expr::Object(ObjectLit {
-        span: DUMMY_SP,
-        props: vec![
-          "isWeb",
-          "isClient",
-          "isNode", 
-          "isWeex",
-          "isKraken",
-          "isMiniApp",
-          "isByteDanceMicroApp",
-          "isBaiduSmartProgram",
-          "isKuaiShouMiniProgram",
-          "isWeChatMiniProgram",
-          "isQuickApp",
-          "isPHA",
-          "isWindVane",
-          "isFRM",
-        ]
-        .into_iter()
-        .map(|target| {
-          PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-            key: PropName::Ident(Ident::new(target.into(), DUMMY_SP, SyntaxContext::empty()).into()),
-            value: Box::new(get_env_expr(&Ident::new(target.into(), DUMMY_SP, SyntaxContext::empty()))),
-          })))
-        })
-        .collect(),
+        span,
+        props: flags
+          .flags
+          .iter()
+          .map(|(target, _)| {
+            let target = Ident::new(target.as_str().into(), span, SyntaxContext::empty());
+            PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+              value: Box::new(get_env_expr(flags, &target, span)),
+              key: PropName::Ident(target.into()),
+            })))
+          })
+          .collect(),
       }))),
       definite: false,
     }],
   })))
 }
 
-fn get_env_stmt(sources: &[String], decls: Vec<VarDeclarator>) -> Vec<Stmt> {
+// `export { isWeb as web, default as env } from 'universal-env'` declares each flag
+// locally and exports it under the original name. `declared` holds the locals already
+// declared in the module, so re-exporting a flag or the flags object twice reuses them.
+fn create_env_reexport(
+  flags: &EnvFlags,
+  specifiers: &[ExportSpecifier],
+  span: Span,
+  declared: &mut HashSet<Atom>,
+) -> (Vec<Stmt>, ModuleItem) {
+  let mut stmts = vec![];
+  let mut export_specifiers = vec![];
+  for specifier in specifiers {
+    let span = specifier.span();
+    let (flag, exported) = match specifier {
+      ExportSpecifier::Named(ExportNamedSpecifier { orig, exported, .. }) => {
+        (Some(orig.atom().clone()), exported.clone().unwrap_or_else(|| orig.clone()))
+      }
+      ExportSpecifier::Namespace(ExportNamespaceSpecifier { name, .. }) => (None, name.clone()),
+      ExportSpecifier::Default(ExportDefaultSpecifier { exported }) => {
+        (None, ModuleExportName::Ident(exported.clone()))
+      }
+    };
+    let local = match flag.as_deref() {
+      Some("default") | None => Ident::from("__env__"),
+      Some(flag) => Ident::from(format!(
+        "__env_{}__",
+        flag.replace(|c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '$', "_")
+      )),
+    };
+    if declared.insert(local.sym.clone()) {
+      stmts.push(match flag.as_deref() {
+        Some("default") | None => create_env_default_export(flags, local.clone(), span),
+        Some(flag) => create_env_declare(flags, &Ident::from(flag), &local, span),
+      });
+    }
+    export_specifiers.push(ExportSpecifier::Named(ExportNamedSpecifier {
+      span,
+      orig: ModuleExportName::Ident(local),
+      exported: Some(exported),
+      is_type_only: false,
+    }));
+  }
+  let export = ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(NamedExport {
+    span,
+    specifiers: export_specifiers,
+    src: None,
+    type_only: false,
+    with: None,
+  }));
+  (stmts, export)
+}
+
+// Names the module exports itself, which take precedence over `export *`
+fn explicit_exports(items: &[ModuleItem]) -> HashSet<String> {
+  let mut names = HashSet::new();
+  for item in items {
+    match item {
+      ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl { decl, .. })) => match decl {
+        Decl::Fn(FnDecl { ident, .. }) | Decl::Class(ClassDecl { ident, .. }) => {
+          names.insert(ident.sym.to_string());
+        }
+        Decl::Var(var) => names.extend(
+          find_pat_ids::<_, Id>(&var.decls)
+            .into_iter()
+            .map(|(sym, _)| sym.to_string()),
+        ),
+        _ => {}
+      },
+      ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(NamedExport { specifiers, .. })) => {
+        names.extend(specifiers.iter().map(exported_name));
+      }
+      _ => {}
+    }
+  }
+  names
+}
+
+fn get_env_stmt(sources: &[String], flags: &EnvFlags, decls: Vec<VarDeclarator>) -> Vec<Stmt> {
   let mut stmts = vec![];
   for decl in decls {
     if let Some(init) = decl.init {
@@ -267,29 +485,29 @@ fn get_env_stmt(sources: &[String], decls: Vec<VarDeclarator>) -> Vec<Stmt> {
               if sources.iter().any(|s| value == s) {
                 match &decl.name {
                   Pat::Ident(BindingIdent { id, .. }) => {
-                    stmts.push(create_env_default_export(id.clone()));
+                    stmts.push(create_env_default_export(flags, id.clone(), decl.span));
                   }
                   Pat::Object(ObjectPat { props, .. }) => {
                       props.iter().for_each(|prop| match prop {
                         ObjectPatProp::Assign(AssignPatProp { key, value, .. }) => {
                           if value.is_some() {
                             if let Expr::Ident(ident) = &**value.as_ref().unwrap() {
-                              stmts.push(create_env_declare(key, ident));
+                              stmts.push(create_env_declare(flags, key, ident, prop.span()));
                             }
                           } else {
-                            stmts.push(create_env_declare(key, key));
+                            stmts.push(create_env_declare(flags, key, key, prop.span()));
                           }
                         }
                         ObjectPatProp::KeyValue(KeyValuePatProp { key, value, .. }) => {
                           if let Pat::Ident(BindingIdent { id, .. }) = &**value {
                             if let PropName::Ident(i) = key {
-                              stmts.push(create_env_declare(&Ident::from(i.as_ref()), id));
+                              stmts.push(create_env_declare(flags, &Ident::from(i.as_ref()), id, prop.span()));
                             }
                           }
                         }
                         ObjectPatProp::Rest(RestPat { arg, .. }) => {
                           if let Pat::Ident(BindingIdent { id, .. }) = &**arg {
-                            stmts.push(create_env_default_export(id.clone()));
+                            stmts.push(create_env_default_export(flags, id.clone(), prop.span()));
                           }
                         }
                       });
@@ -310,63 +528,114 @@ fn get_env_stmt(sources: &[String], decls: Vec<VarDeclarator>) -> Vec<Stmt> {
 impl Fold for EnvReplacementImpl {
   fn fold_module_items(&mut self, items: Vec<ModuleItem>) -> Vec<ModuleItem> {
     let mut new_module_items: Vec<ModuleItem> = vec![];
+    let mut reexported = HashSet::new();
+    let explicit_exports = explicit_exports(&items);
     for item in items.iter() {
-      match &item {
-        ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl)) => {
-          let src = &import_decl.src.value;
-          if self.sources.iter().any(|s| src == s) {
-            import_decl
-              .specifiers
-              .iter()
-              .for_each(|specifier| match specifier {
-                ImportSpecifier::Named(named_specifier) => {
-                  let imported = match &named_specifier.imported {
-                    Some(ModuleExportName::Ident(ident)) => Some(ident),
-                    _ => None,
-                  };
-                  let s = if let Some(imported) = imported {
-                    imported
-                  } else {
-                    &named_specifier.local
-                  };
-                  new_module_items.push(ModuleItem::Stmt(create_env_declare(
-                    s,
-                    &named_specifier.local,
-                  )));
-                }
-                ImportSpecifier::Default(default_specifier) => {
-                  new_module_items.push(ModuleItem::Stmt(create_env_default_export(
-                    default_specifier.local.clone(),
-                  )));
-                }
-                ImportSpecifier::Namespace(namespace_specifier) => {
-                  new_module_items.push(ModuleItem::Stmt(create_env_default_export(
-                    namespace_specifier.local.clone(),
-                  )));
-                }
-              });
-          } else {
-            new_module_items.push(item.clone());
-          }
+      let env_stmts = match &item {
+        ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl))
+          if self.sources.iter().any(|s| import_decl.src.value == *s) =>
+        {
+          import_decl
+            .specifiers
+            .iter()
+            .map(|specifier| match specifier {
+              ImportSpecifier::Named(named_specifier) => {
+                let imported = match &named_specifier.imported {
+                  Some(ModuleExportName::Ident(ident)) => Some(ident),
+                  _ => None,
+                };
+                let s = if let Some(imported) = imported {
+                  imported
+                } else {
+                  &named_specifier.local
+                };
+                create_env_declare(&self.flags, s, &named_specifier.local, named_specifier.span)
+              }
+              ImportSpecifier::Default(default_specifier) => {
+                create_env_default_export(
+                  &self.flags,
+                  default_specifier.local.clone(),
+                  default_specifier.span,
+                )
+              }
+              ImportSpecifier::Namespace(namespace_specifier) => {
+                create_env_default_export(
+                  &self.flags,
+                  namespace_specifier.local.clone(),
+                  namespace_specifier.span,
+                )
+              }
+            })
+            .collect()
         }
-        ModuleItem::Stmt(Stmt::Decl(Decl::Var(var_decl))) => {
-          let stmt = get_env_stmt(&self.sources, var_decl.decls.clone());
-          if !stmt.is_empty() {
-            let module_stmts = stmt
-              .into_iter()
-              .map(ModuleItem::Stmt)
-              .collect::<Vec<ModuleItem>>();
-            new_module_items.extend_from_slice(&module_stmts);
-          } else {
-            new_module_items.push(item.clone());
-          }
+        ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(NamedExport {
+          span,
+          src: Some(src),
+          specifiers,
+          type_only: false,
+          ..
+        })) if self.sources.iter().any(|s| src.value == *s) => {
+          let (env_stmts, export) = create_env_reexport(&self.flags, specifiers, *span, &mut reexported);
+          new_module_items.extend(env_stmts.into_iter().map(ModuleItem::Stmt));
+          new_module_items.push(export);
+          continue;
         }
-        _ => {
-          new_module_items.push(item.clone().fold_with(self));
+        // `export *` re-exports every flag, but not the default export
+        ModuleItem::ModuleDecl(ModuleDecl::ExportAll(ExportAll {
+          span,
+          src,
+          type_only: false,
+          ..
+        })) if self.sources.iter().any(|s| src.value == *s) => {
+          let specifiers = self
+            .flags
+            .names()
+            .filter(|flag| !explicit_exports.contains(*flag))
+            .map(|flag| {
+              ExportSpecifier::Named(ExportNamedSpecifier {
+                span: *span,
+                orig: ModuleExportName::Ident(Ident::new(flag.into(), *span, SyntaxContext::empty())),
+                exported: None,
+                is_type_only: false,
+              })
+            })
+            .collect::<Vec<_>>();
+          let (env_stmts, export) = create_env_reexport(&self.flags, &specifiers, *span, &mut reexported);
+          new_module_items.extend(env_stmts.into_iter().map(ModuleItem::Stmt));
+          new_module_items.push(export);
+          continue;
         }
+        ModuleItem::Stmt(Stmt::Decl(Decl::Var(var_decl))) => {
+          get_env_stmt(&self.sources, &self.flags, var_decl.decls.clone())
+        }
+        _ => vec![],
+      };
+      if env_stmts.is_empty() {
+        new_module_items.push(item.clone());
+      } else {
+        self.record_bindings(env_stmts.iter());
+        new_module_items.extend(env_stmts.into_iter().map(ModuleItem::Stmt));
       }
     }
+    // Fold the rest once every imported flag is known, as imports are hoisted
+    let new_module_items = new_module_items
+      .into_iter()
+      .map(|item| item.fold_with(self))
+      .collect::<Vec<_>>();
+
+    // Drop flag objects only read through `env.isX`, which were rewritten above
+    let mut counter = IdentCounter::default();
+    new_module_items.visit_with(&mut counter);
     new_module_items
+      .into_iter()
+      .filter(|item| match item {
+        ModuleItem::Stmt(Stmt::Decl(Decl::Var(var_decl))) => !var_decl.decls.iter().all(|decl| {
+          matches!(&decl.name, Pat::Ident(BindingIdent { id, .. })
+            if self.namespaces.contains(&id.to_id()) && counter.counts.get(&id.to_id()) == Some(&1))
+        }),
+        _ => true,
+      })
+      .collect()
   }
 
   fn fold_block_stmt(&mut self, block: BlockStmt) -> BlockStmt {
@@ -377,11 +646,12 @@ impl Fold for EnvReplacementImpl {
       .into_iter()
       .for_each(|stmt| match &stmt {
         Stmt::Decl(Decl::Var(var_decl)) => {
-          let env_stmts = get_env_stmt(&self.sources, var_decl.decls.clone());
+          let env_stmts = get_env_stmt(&self.sources, &self.flags, var_decl.decls.clone());
           if !env_stmts.is_empty() {
+            self.record_bindings(env_stmts.iter());
             new_stmts.extend_from_slice(&env_stmts);
           } else {
-            new_stmts.push(stmt);
+            new_stmts.push(stmt.fold_with(self));
           }
         }
         _ => {
@@ -393,17 +663,218 @@ impl Fold for EnvReplacementImpl {
       ..block
     }
   }
+
+  fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+    let stmt = stmt.fold_children_with(self);
+    let Stmt::If(IfStmt {
+      span,
+      test,
+      cons,
+      alt,
+    }) = stmt
+    else {
+      return stmt;
+    };
+    match self.evaluate(&test) {
+      Some(value) => {
+        let (kept, removed) = if value { (Some(cons), alt) } else { (alt, Some(cons)) };
+        let hoisted = removed.and_then(|removed| hoisted_var_decl(&removed));
+        match (kept, hoisted) {
+          (Some(kept), None) => *kept,
+          (kept, hoisted) => Stmt::Block(BlockStmt {
+            span,
+            ctxt: Default::default(),
+            stmts: hoisted.into_iter().chain(kept.map(|kept| *kept)).collect(),
+          }),
+        }
+      }
+      None => Stmt::If(IfStmt {
+        span,
+        test,
+        cons,
+        alt,
+      }),
+    }
+  }
+
+  fn fold_expr(&mut self, expr: Expr) -> Expr {
+    let expr = expr.fold_children_with(self);
+    match expr {
+      // `env.isWeb` on a flag object
+      Expr::Member(member) => match self.get_member_flag(&member) {
+        Some(flag) => get_env_expr(&self.flags, &Ident::from(flag), member.span),
+        None => Expr::Member(member),
+      },
+      Expr::Cond(CondExpr { test, cons, alt, .. }) if self.evaluate(&test).is_some() => {
+        if self.evaluate(&test) == Some(true) {
+          *cons
+        } else {
+          *alt
+        }
+      }
+      expr => expr,
+    }
+  }
+}
+
+impl EnvReplacementImpl {
+  // Remember the `var isX = true/false` and flag object declarations emitted for flags
+  fn record_bindings<'a>(&mut self, stmts: impl Iterator<Item = &'a Stmt>) {
+    for stmt in stmts {
+      let Stmt::Decl(Decl::Var(var_decl)) = stmt else {
+        continue;
+      };
+      for decl in &var_decl.decls {
+        if let (Pat::Ident(BindingIdent { id, .. }), Some(init)) = (&decl.name, &decl.init) {
+          match &**init {
+            Expr::Lit(Lit::Bool(Bool { value, .. })) => {
+              self.constants.insert(id.to_id(), *value);
+            }
+            Expr::Object(_) => {
+              self.namespaces.insert(id.to_id());
+            }
+            _ => {}
+          }
+        }
+      }
+    }
+  }
+
+  fn get_member_flag(&self, member: &MemberExpr) -> Option<Atom> {
+    let obj = member.obj.as_ident()?;
+    if !self.namespaces.contains(&obj.to_id()) {
+      return None;
+    }
+    let name = match &member.prop {
+      MemberProp::Ident(prop) => prop.sym.clone(),
+      MemberProp::Computed(ComputedPropName { expr, .. }) => match &**expr {
+        Expr::Lit(Lit::Str(Str { value, .. })) => value.clone(),
+        _ => return None,
+      },
+      MemberProp::PrivateName(_) => return None,
+    };
+    // Anything else, e.g. `env.hasOwnProperty`, is a property of the object itself
+    self.flags.is_flag(&name).then_some(name)
+  }
+
+  // Statically known value of a condition built from folded flags
+  fn evaluate(&self, expr: &Expr) -> Option<bool> {
+    match expr {
+      Expr::Lit(Lit::Bool(Bool { value, .. })) => Some(*value),
+      Expr::Ident(ident) => self.constants.get(&ident.to_id()).copied(),
+      Expr::Paren(ParenExpr { expr, .. }) => self.evaluate(expr),
+      Expr::Unary(UnaryExpr {
+        op: UnaryOp::Bang,
+        arg,
+        ..
+      }) => self.evaluate(arg).map(|value| !value),
+      Expr::Bin(BinExpr {
+        op: op @ (BinaryOp::LogicalAnd | BinaryOp::LogicalOr),
+        left,
+        right,
+        ..
+      }) => {
+        // `false && x` and `true || x` are known without `x`
+        let short_circuit = *op == BinaryOp::LogicalOr;
+        match self.evaluate(left)? {
+          value if value == short_circuit => Some(value),
+          _ => self.evaluate(right),
+        }
+      }
+      _ => None,
+    }
+  }
+}
+
+// `var` declarations in a removed branch are hoisted, so keep them declared
+fn hoisted_var_decl(stmt: &Stmt) -> Option<Stmt> {
+  let mut collector = VarCollector::default();
+  stmt.visit_with(&mut collector);
+  if collector.ids.is_empty() {
+    return None;
+  }
+  Some(Stmt::Decl(Decl::Var(Box::new(VarDecl {
+    span: stmt.span(),
+    ctxt: Default::default(),
+    kind: VarDeclKind::Var,
+    declare: false,
+    decls: collector
+      .ids
+      .into_iter()
+      .map(|id| VarDeclarator {
+        span: id.span,
+        name: Pat::Ident(id.into()),
+        init: None,
+        definite: false,
+      })
+      .collect(),
+  }))))
 }
 
-pub fn env_replacement(sources: Vec<String>) -> impl swc_core::ecma::ast::Pass {
-  fold_pass(EnvReplacementImpl { sources })
+#[derive(Default)]
+struct IdentCounter {
+  counts: HashMap<Id, usize>,
+}
+
+impl Visit for IdentCounter {
+  fn visit_ident(&mut self, ident: &Ident) {
+    *self.counts.entry(ident.to_id()).or_default() += 1;
+  }
+}
+
+#[derive(Default)]
+struct VarCollector {
+  ids: Vec<Ident>,
+}
+
+impl Visit for VarCollector {
+  fn visit_var_decl(&mut self, var_decl: &VarDecl) {
+    if var_decl.kind == VarDeclKind::Var {
+      for decl in &var_decl.decls {
+        self.visit_pat(&decl.name);
+      }
+    }
+    for decl in &var_decl.decls {
+      decl.init.visit_with(self);
+    }
+  }
+
+  fn visit_pat(&mut self, pat: &Pat) {
+    match pat {
+      Pat::Ident(binding) => self.ids.push(binding.id.clone()),
+      _ => pat.visit_children_with(self),
+    }
+  }
+
+  // Function scoped declarations do not leak out
+  fn visit_function(&mut self, _: &Function) {}
+
+  fn visit_arrow_expr(&mut self, _: &ArrowExpr) {}
+}
+
+pub fn env_replacement(config: EnvReplacementConfig) -> impl swc_core::ecma::ast::Pass {
+  let (sources, flags) = match config {
+    EnvReplacementConfig::Sources(sources) => (sources, get_env_flags(Default::default(), None, None)),
+    EnvReplacementConfig::Options {
+      sources,
+      flags,
+      renderer,
+      target,
+    } => (sources, get_env_flags(flags, renderer, target)),
+  };
+  fold_pass(EnvReplacementImpl {
+    sources,
+    flags,
+    constants: Default::default(),
+    namespaces: Default::default(),
+  })
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
   use swc_core::{
-    common::{FileName, SourceMap},
+    common::{FileName, SourceMap, DUMMY_SP},
     ecma::{
       parser::{lexer::Lexer, Parser, StringInput, Syntax},
       visit::FoldWith,
@@ -423,6 +894,24 @@ mod tests {
     parser.parse_module().expect("Failed to parse module")
   }
 
+  fn create_transform(config: &str) -> EnvReplacementImpl {
+    let (sources, flags) = match serde_json::from_str(config).unwrap() {
+      EnvReplacementConfig::Sources(sources) => (sources, get_env_flags(Default::default(), None, None)),
+      EnvReplacementConfig::Options {
+        sources,
+        flags,
+        renderer,
+        target,
+      } => (sources, get_env_flags(flags, renderer, target)),
+    };
+    EnvReplacementImpl {
+      sources,
+      flags,
+      constants: Default::default(),
+      namespaces: Default::default(),
+    }
+  }
+
   #[test]
   fn test_env_replacement_named_import() {
     let code = r#"import { isClient, isServer } from 'env';"#;
@@ -430,9 +919,7 @@ mod tests {
 var isServer = import.meta.renderer === "server";"#;
     
     let mut module = parse_js(code);
-    let mut transform = EnvReplacementImpl {
-      sources: vec!["env".to_string()],
-    };
+    let mut transform = create_transform(r#"["env"]"#);
     module = module.fold_with(&mut transform);
     
     // This is a basic structural test
@@ -441,14 +928,26 @@ var isServer = import.meta.renderer === "server";"#;
 
   #[test]
   fn test_env_replacement_default_import() {
-    let code = r#"import env from 'env';"#;
+    let code = r#"import env from 'env';
+console.log(env);"#;
     
     let mut module = parse_js(code);
-    let mut transform = EnvReplacementImpl {
-      sources: vec!["env".to_string()],
-    };
+    let mut transform = create_transform(r#"["env"]"#);
     module = module.fold_with(&mut transform);
     
+    assert_eq!(module.body.len(), 2);
+  }
+
+  #[test]
+  fn test_env_replacement_member_access() {
+    let code = r#"import * as env from 'env';
+console.log(env.isWeb, env['isNode']);"#;
+
+    let mut module = parse_js(code);
+    let mut transform = create_transform(r#"["env"]"#);
+    module = module.fold_with(&mut transform);
+
+    // The flag object is dropped once every access is rewritten
     assert_eq!(module.body.len(), 1);
   }
 
@@ -457,9 +956,7 @@ var isServer = import.meta.renderer === "server";"#;
     let code = r#"import { isWeb, isPHA, isWindVane } from 'env';"#;
     
     let mut module = parse_js(code);
-    let mut transform = EnvReplacementImpl {
-      sources: vec!["env".to_string()],
-    };
+    let mut transform = create_transform(r#"["env"]"#);
     module = module.fold_with(&mut transform);
     
     assert_eq!(module.body.len(), 3);
@@ -470,11 +967,96 @@ var isServer = import.meta.renderer === "server";"#;
     let code = r#"const { isClient } = require('env');"#;
     
     let mut module = parse_js(code);
-    let mut transform = EnvReplacementImpl {
-      sources: vec!["env".to_string()],
-    };
+    let mut transform = create_transform(r#"["env"]"#);
     module = module.fold_with(&mut transform);
     
     assert_eq!(module.body.len(), 1);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_env_replacement_custom_flags() {
+    let config: EnvReplacementConfig = serde_json::from_str(
+      r#"{
+        "sources": ["env"],
+        "flags": {
+          "isInHouse": { "renderer": "client", "target": "web", "globals": ["inHouse.bridge"] },
+          "isWeex": { "target": "weex" }
+        }
+      }"#,
+    )
+    .unwrap();
+    let EnvReplacementConfig::Options { sources, flags, .. } = config else {
+      panic!("expected flags");
+    };
+    let flags = get_env_flags(flags, None, None);
+
+    let is_in_house = get_env_expr(&flags, &Ident::from("isInHouse"), DUMMY_SP);
+    let Expr::Bin(BinExpr { left, right, .. }) = &is_in_house else {
+      panic!("expected a combined check");
+    };
+    assert!(matches!(&**left, Expr::Bin(BinExpr { op: BinaryOp::LogicalAnd, .. })));
+    let Expr::Bin(BinExpr { left: type_of, op, .. }) = &**right else {
+      panic!("expected a typeof check");
+    };
+    assert_eq!(*op, BinaryOp::NotEqEq);
+    assert!(matches!(&**type_of, Expr::Unary(UnaryExpr { arg, .. }) if arg.is_member()));
+
+    // Overridden built-in flags keep their place in the table
+    assert_eq!(flags.flags[3].0, "isWeex");
+    assert!(get_env_expr(&flags, &Ident::from("isWeex"), DUMMY_SP).is_bin());
+    assert_eq!(flags.flags.last().unwrap().0, "isInHouse");
+
+    let mut module = parse_js(r#"import env from 'env';
+console.log(env);"#);
+    module = module.fold_with(&mut EnvReplacementImpl {
+      sources,
+      flags,
+      constants: Default::default(),
+      namespaces: Default::default(),
+    });
+    let ModuleItem::Stmt(Stmt::Decl(Decl::Var(var_decl))) = &module.body[0] else {
+      panic!("expected a declaration");
+    };
+    let Some(Expr::Object(object)) = var_decl.decls[0].init.as_deref() else {
+      panic!("expected an object");
+    };
+    assert_eq!(object.props.len(), get_builtin_flags().len() + 1);
+  }
+
+  #[test]
+  fn test_env_replacement_unknown_flag() {
+    let flags = get_env_flags(Default::default(), None, None);
+    assert!(matches!(
+      get_env_expr(&flags, &Ident::from("isUnknown"), DUMMY_SP),
+      Expr::Lit(Lit::Bool(Bool { value: false, .. }))
+    ));
+    assert!(matches!(
+      get_env_expr(&flags, &Ident::from("isMiniApp"), DUMMY_SP),
+      Expr::Lit(Lit::Bool(Bool { value: false, .. }))
+    ));
+    assert!(get_env_expr(&flags, &Ident::from("isServer"), DUMMY_SP).is_bin());
+  }
+
+  #[test]
+  fn test_env_replacement_static_flags() {
+    let flags = get_env_flags(Default::default(), Some("client".to_string()), Some("web".to_string()));
+    let is_bool = |name: &str, expected: bool| {
+      matches!(
+        get_env_expr(&flags, &Ident::from(name), DUMMY_SP),
+        Expr::Lit(Lit::Bool(Bool { value, .. })) if value == expected
+      )
+    };
+    assert!(is_bool("isWeb", true));
+    assert!(is_bool("isClient", true));
+    assert!(is_bool("isServer", false));
+    assert!(is_bool("isWeex", false));
+    // Runtime checks are kept for what the target does not tell
+    assert!(get_env_expr(&flags, &Ident::from("isPHA"), DUMMY_SP).is_bin());
+
+    let flags = get_env_flags(Default::default(), Some("server".to_string()), None);
+    assert!(matches!(
+      get_env_expr(&flags, &Ident::from("isWeb"), DUMMY_SP),
+      Expr::Lit(Lit::Bool(Bool { value: false, .. }))
+    ));
+  }
+}
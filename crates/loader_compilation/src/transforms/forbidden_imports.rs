@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+  options::NameMatcher,
+  transformer::{ForbiddenImport, SharedTransformReport},
+  transforms::keep_export::{cjs_export, cjs_prop_name, exported_name, module_bindings, CjsExport},
+};
+use swc_core::ecma::{
+  ast::*,
+  utils::find_pat_ids,
+  visit::{Visit, VisitWith},
+};
+
+// Ids referenced anywhere in a node, bindings included, which only over-approximates the graph
+#[derive(Default)]
+struct RefCollector {
+  refs: HashSet<Id>,
+}
+
+impl Visit for RefCollector {
+  fn visit_ident(&mut self, ident: &Ident) {
+    self.refs.insert(ident.to_id());
+  }
+}
+
+fn collect_refs<T: VisitWith<RefCollector>>(node: &T) -> HashSet<Id> {
+  let mut collector = RefCollector::default();
+  node.visit_with(&mut collector);
+  collector.refs
+}
+
+// Each binding declared by `decl`, along with the ids its initializer or body references
+fn declared_refs(decl: &Decl) -> Vec<(Id, HashSet<Id>)> {
+  match decl {
+    Decl::Fn(FnDecl { ident, function, .. }) => vec![(ident.to_id(), collect_refs(function))],
+    Decl::Class(ClassDecl { ident, class, .. }) => vec![(ident.to_id(), collect_refs(class))],
+    Decl::Var(var) => var
+      .decls
+      .iter()
+      .flat_map(|declarator| {
+        let refs = collect_refs(declarator);
+        find_pat_ids::<_, Id>(&declarator.name)
+          .into_iter()
+          .map(move |id| (id, refs.clone()))
+      })
+      .collect(),
+    _ => vec![],
+  }
+}
+
+/// Runs after `removeExport` and records every import matching `forbiddenImports` that
+/// survived, with the exports or top-level code still referencing it.
+pub struct ForbiddenImportsCheck {
+  forbidden: NameMatcher,
+  report: SharedTransformReport,
+}
+
+impl ForbiddenImportsCheck {
+  fn check(&self, module: &Module) -> Vec<ForbiddenImport> {
+    let mut found = vec![];
+    // Sources in import order, with the local bindings of each
+    let mut sources: Vec<String> = vec![];
+    let mut forbidden_locals: HashMap<Id, String> = HashMap::new();
+    for item in &module.body {
+      let ModuleItem::ModuleDecl(ModuleDecl::Import(import)) = item else {
+        continue;
+      };
+      let source = import.src.value.to_string();
+      if !self.forbidden.is_match(&source) {
+        continue;
+      }
+      if import.specifiers.is_empty() {
+        found.push(ForbiddenImport {
+          source,
+          kept_by: vec!["a side-effect import".to_string()],
+        });
+        continue;
+      }
+      if !sources.contains(&source) {
+        sources.push(source.clone());
+      }
+      for specifier in &import.specifiers {
+        let local = match specifier {
+          ImportSpecifier::Named(named) => &named.local,
+          ImportSpecifier::Default(default) => &default.local,
+          ImportSpecifier::Namespace(namespace) => &namespace.local,
+        };
+        forbidden_locals.insert(local.to_id(), source.clone());
+      }
+    }
+    if forbidden_locals.is_empty() {
+      return found;
+    }
+
+    // Top-level bindings with the ids they reference, and what keeps the module alive
+    let bindings = module_bindings(module);
+    let mut decls: HashMap<Id, HashSet<Id>> = HashMap::new();
+    let mut roots: Vec<(String, HashSet<Id>)> = vec![];
+    for item in &module.body {
+      match item {
+        ModuleItem::ModuleDecl(ModuleDecl::Import(_)) => {}
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl { decl, .. })) => {
+          for (id, refs) in declared_refs(decl) {
+            roots.push((format!("export `{}`", id.0), refs.clone()));
+            decls.insert(id, refs);
+          }
+        }
+        ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(NamedExport {
+          specifiers,
+          src: None,
+          ..
+        })) => {
+          for specifier in specifiers {
+            if let ExportSpecifier::Named(ExportNamedSpecifier {
+              orig: ModuleExportName::Ident(orig),
+              ..
+            }) = specifier
+            {
+              roots.push((
+                format!("export `{}`", exported_name(specifier)),
+                HashSet::from([orig.to_id()]),
+              ));
+            }
+          }
+        }
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(_))
+        | ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(_)) => {
+          roots.push(("export `default`".to_string(), collect_refs(item)));
+        }
+        ModuleItem::Stmt(Stmt::Decl(decl)) => decls.extend(declared_refs(decl)),
+        ModuleItem::Stmt(Stmt::Expr(ExprStmt { expr, .. })) if cjs_export(expr, &bindings).is_some() => {
+          let Expr::Assign(assign) = &**expr else {
+            continue;
+          };
+          match (cjs_export(expr, &bindings), &*assign.right) {
+            (Some(CjsExport::Named(name)), _) => {
+              roots.push((format!("export `{name}`"), collect_refs(&assign.right)));
+            }
+            (_, Expr::Object(obj)) => {
+              for prop in &obj.props {
+                let root = match cjs_prop_name(prop) {
+                  Some(name) => format!("export `{name}`"),
+                  None => "top-level code".to_string(),
+                };
+                roots.push((root, collect_refs(prop)));
+              }
+            }
+            _ => roots.push(("export `default`".to_string(), collect_refs(&assign.right))),
+          }
+        }
+        _ => roots.push(("top-level code".to_string(), collect_refs(item))),
+      }
+    }
+
+    let mut kept_by: HashMap<&String, Vec<String>> = HashMap::new();
+    for (root, refs) in &roots {
+      let mut reached: HashSet<&Id> = HashSet::new();
+      let mut queue: Vec<&Id> = refs.iter().collect();
+      while let Some(id) = queue.pop() {
+        if !reached.insert(id) {
+          continue;
+        }
+        if let Some(source) = forbidden_locals.get(id) {
+          let roots = kept_by.entry(source).or_default();
+          if !roots.contains(root) {
+            roots.push(root.clone());
+          }
+        }
+        if let Some(refs) = decls.get(id) {
+          queue.extend(refs);
+        }
+      }
+    }
+
+    for source in sources {
+      let kept_by = kept_by.remove(&source).unwrap_or_default();
+      found.push(ForbiddenImport { source, kept_by });
+    }
+    found
+  }
+}
+
+impl Pass for ForbiddenImportsCheck {
+  fn process(&mut self, program: &mut Program) {
+    if let Program::Module(module) = program {
+      let found = self.check(module);
+      self.report.borrow_mut().forbidden_imports.extend(found);
+    }
+  }
+}
+
+pub fn forbidden_imports(forbidden: NameMatcher, report: SharedTransformReport) -> ForbiddenImportsCheck {
+  ForbiddenImportsCheck { forbidden, report }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{options::NamePattern, transforms::remove_export::remove_export};
+  use swc_core::{
+    common::{FileName, SourceMap},
+    ecma::parser::{lexer::Lexer, Parser, StringInput, Syntax},
+  };
+
+  fn parse_js(code: &str) -> Program {
+    let cm = SourceMap::default();
+    let fm = cm.new_source_file(FileName::Anon.into(), code.to_string());
+    let lexer = Lexer::new(
+      Syntax::Es(Default::default()),
+      Default::default(),
+      StringInput::from(&*fm),
+      None,
+    );
+    let mut parser = Parser::new_from(lexer);
+    Program::Module(parser.parse_module().expect("Failed to parse module"))
+  }
+
+  fn check(code: &str, remove_exports: &[&str], forbidden: &[&str]) -> Vec<ForbiddenImport> {
+    let report = SharedTransformReport::default();
+    let forbidden = forbidden
+      .iter()
+      .map(|pattern| NamePattern::Name(pattern.to_string()))
+      .collect();
+    let mut program = parse_js(code);
+    program.mutate(remove_export(
+      remove_exports.iter().map(|name| name.to_string()).collect::<Vec<_>>().into(),
+      report.clone(),
+    ));
+    program.mutate(forbidden_imports(
+      NameMatcher::new(forbidden, "removeExport.forbiddenImports").unwrap(),
+      report.clone(),
+    ));
+    let found = std::mem::take(&mut report.borrow_mut().forbidden_imports);
+    found
+  }
+
+  #[test]
+  fn test_pruned_imports_pass() {
+    let found = check(
+      r#"import fs from 'fs';
+import { query } from '@/server/db';
+export const getServerData = () => query(fs.readFileSync('sql'));
+export default function Page() { return null; }"#,
+      &["getServerData"],
+      &["fs", "@/server/**"],
+    );
+
+    assert!(found.is_empty(), "{found:?}");
+  }
+
+  #[test]
+  fn test_import_kept_by_export() {
+    let found = check(
+      r#"import fs from 'fs';
+import { query } from '@/server/db';
+const load = () => fs.readFileSync('data.json');
+export const getServerData = () => query();
+export function getConfig() { return load(); }
+export { load as loader };
+export default function Page() { return null; }"#,
+      &["getServerData"],
+      &["fs", "@/server/**"],
+    );
+
+    assert_eq!(
+      found,
+      vec![ForbiddenImport {
+        source: "fs".to_string(),
+        kept_by: vec!["export `getConfig`".to_string(), "export `loader`".to_string()],
+      }]
+    );
+  }
+
+  #[test]
+  fn test_import_kept_by_commonjs_export() {
+    let found = check(
+      r#"import fs from 'fs';
+exports.getServerData = () => fs.readFileSync('data.json');
+module.exports.getConfig = () => fs.existsSync('config.json');"#,
+      &["getServerData"],
+      &["fs"],
+    );
+
+    assert_eq!(
+      found,
+      vec![ForbiddenImport {
+        source: "fs".to_string(),
+        kept_by: vec!["export `getConfig`".to_string()],
+      }]
+    );
+  }
+
+  #[test]
+  fn test_import_kept_by_top_level_code() {
+    let found = check(
+      r#"import './server-only';
+import { init } from 'server-sdk';
+init();
+export const getServerData = () => 1;"#,
+      &["getServerData"],
+      &["server-only", "./server-only", "server-sdk"],
+    );
+
+    assert_eq!(
+      found,
+      vec![
+        ForbiddenImport {
+          source: "./server-only".to_string(),
+          kept_by: vec!["a side-effect import".to_string()],
+        },
+        ForbiddenImport {
+          source: "server-sdk".to_string(),
+          kept_by: vec!["top-level code".to_string()],
+        },
+      ]
+    );
+  }
+}
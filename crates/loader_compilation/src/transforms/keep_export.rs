@@ -1,12 +1,16 @@
 use std::collections::HashSet as FxHashSet;
 use std::mem::take;
+
+use crate::{options::ExportSelector, transformer::SharedTransformReport};
 use swc_core::{
+  atoms::Atom,
   common::{
     pass::{Repeat, Repeated},
     DUMMY_SP,
   },
   ecma::{
     ast::*,
+    utils::collect_decls,
     visit::{noop_fold_type, Fold, FoldWith, fold_pass},
   },
 };
@@ -16,16 +20,23 @@ struct KeepExportState {
   refs_from_other: FxHashSet<Id>,
   refs_used: FxHashSet<Id>,
   should_run_again: bool,
-  keep_exports: Vec<String>,
+  keep_exports: ExportSelector,
+  report: SharedTransformReport,
+  // Everything the module binds, which shadows the CommonJS `module`/`exports`
+  bindings: FxHashSet<Id>,
 }
 
 impl KeepExportState {
   fn should_keep_identifier(&mut self, i: &Ident) -> bool {
-    self.keep_exports.contains(&String::from(&*i.sym))
+    self.keep_exports.is_match(&i.sym)
   }
 
   fn should_keep_default(&mut self) -> bool {
-    self.keep_exports.contains(&String::from("default"))
+    self.keep_exports.is_match("default")
+  }
+
+  fn report_removed_export(&self, name: &str) {
+    self.report.borrow_mut().removed_exports.insert(name.to_string());
   }
 }
 
@@ -91,6 +102,7 @@ impl Fold for KeepExportImpl {
   }
 
   fn fold_module(&mut self, mut m: Module) -> Module {
+    self.state.bindings = module_bindings(&m);
     {
       let mut v = KeepExportAnalyzer {
         state: &mut self.state,
@@ -128,6 +140,12 @@ impl Fold for KeepExportImpl {
       let i = i.fold_with(self);
 
       if i.specifiers.is_empty() {
+        self
+          .state
+          .report
+          .borrow_mut()
+          .removed_imports
+          .insert(i.src.value.to_string());
         return ModuleItem::Stmt(Stmt::Empty(EmptyStmt { span: DUMMY_SP }));
       }
 
@@ -169,6 +187,7 @@ impl Fold for KeepExportImpl {
 
       match preserve {
         false => {
+          self.state.report_removed_export(&exported_name(s));
           if let ExportSpecifier::Named(ExportNamedSpecifier {
             orig: ModuleExportName::Ident(_orig),
             ..
@@ -336,6 +355,58 @@ impl KeepExportAnalyzer<'_> {
 
     e
   }
+
+  fn fold_kept<T: FoldWith<Self>>(&mut self, n: T) -> T {
+    let old_in_kept = self.in_kept_fn;
+    self.in_kept_fn = true;
+    let n = n.fold_with(self);
+    self.in_kept_fn = old_in_kept;
+    n
+  }
+
+  // Top-level expressions are dropped, except the CommonJS exports that are kept
+  fn fold_cjs_export(&mut self, mut s: ExprStmt) -> ModuleItem {
+    let empty = ModuleItem::Stmt(Stmt::Empty(EmptyStmt { span: DUMMY_SP }));
+    let Some(export) = cjs_export(&s.expr, &self.state.bindings) else {
+      return empty;
+    };
+    let Expr::Assign(assign) = &mut *s.expr else {
+      return empty;
+    };
+
+    match export {
+      CjsExport::Named(name) => {
+        if !self.state.keep_exports.is_match(&name) {
+          self.state.report_removed_export(&name);
+          return empty;
+        }
+        assign.right = self.fold_kept(take(&mut assign.right));
+      }
+      CjsExport::Module => match &mut *assign.right {
+        Expr::Object(obj) => {
+          obj.props = take(&mut obj.props)
+            .into_iter()
+            .filter_map(|prop| match cjs_prop_name(&prop) {
+              Some(name) if !self.state.keep_exports.is_match(&name) => {
+                self.state.report_removed_export(&name);
+                None
+              }
+              _ => Some(self.fold_kept(prop)),
+            })
+            .collect();
+        }
+        _ => {
+          if !self.state.should_keep_default() {
+            self.state.report_removed_export("default");
+            return empty;
+          }
+          assign.right = self.fold_kept(take(&mut assign.right));
+        }
+      },
+    }
+
+    ModuleItem::Stmt(Stmt::Expr(s))
+  }
 }
 
 impl Fold for KeepExportAnalyzer<'_> {
@@ -455,8 +526,8 @@ impl Fold for KeepExportAnalyzer<'_> {
         return ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(e));
       }
 
-      ModuleItem::Stmt(Stmt::Expr(_e)) => {
-        return ModuleItem::Stmt(Stmt::Empty(EmptyStmt { span: DUMMY_SP }));
+      ModuleItem::Stmt(Stmt::Expr(e)) => {
+        return self.fold_cjs_export(e);
       }
 
       ModuleItem::Stmt(Stmt::If(_e)) => {
@@ -480,6 +551,7 @@ impl Fold for KeepExportAnalyzer<'_> {
             let s = s.fold_children_with(self);
             return s;
           } else {
+            self.state.report_removed_export(&f.ident.sym);
             return ModuleItem::Stmt(Stmt::Empty(EmptyStmt { span: DUMMY_SP }));
           }
         }
@@ -494,6 +566,7 @@ impl Fold for KeepExportAnalyzer<'_> {
               let s = s.fold_children_with(self);
               return s;
             } else {
+              self.state.report_removed_export(&id.id.sym);
               return ModuleItem::Stmt(Stmt::Empty(EmptyStmt { span: DUMMY_SP }));
             }
           }
@@ -504,12 +577,14 @@ impl Fold for KeepExportAnalyzer<'_> {
 
     if let ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(_e)) = &s {
       if !self.state.should_keep_default() {
+        self.state.report_removed_export("default");
         return ModuleItem::Stmt(Stmt::Empty(EmptyStmt { span: DUMMY_SP }));
       }
     }
 
     if let ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(_e)) = &s {
       if !self.state.should_keep_default() {
+        self.state.report_removed_export("default");
         return ModuleItem::Stmt(Stmt::Empty(EmptyStmt { span: DUMMY_SP }));
       }
     }
@@ -549,11 +624,97 @@ impl Fold for KeepExportAnalyzer<'_> {
   }
 }
 
-pub fn keep_export(exports: Vec<String>) -> impl swc_core::ecma::ast::Pass {
+// Name a specifier is exported under
+pub(crate) fn exported_name(specifier: &ExportSpecifier) -> String {
+  match specifier {
+    ExportSpecifier::Namespace(ExportNamespaceSpecifier { name, .. }) => name.atom().to_string(),
+    ExportSpecifier::Default(ExportDefaultSpecifier { exported }) => exported.sym.to_string(),
+    ExportSpecifier::Named(ExportNamedSpecifier { orig, exported, .. }) => {
+      exported.as_ref().unwrap_or(orig).atom().to_string()
+    }
+  }
+}
+
+/// A top-level CommonJS export assignment.
+pub(crate) enum CjsExport {
+  // `exports.X = ...` or `module.exports.X = ...`
+  Named(Atom),
+  // `module.exports = ...`, exporting the object literal's properties or else `default`
+  Module,
+}
+
+// The CommonJS global `name`, unless the module declares a binding of its own with that name
+fn is_cjs_global(e: &Expr, name: &str, bindings: &FxHashSet<Id>) -> bool {
+  matches!(e, Expr::Ident(ident) if ident.sym == name && !bindings.contains(&ident.to_id()))
+}
+
+fn is_module_exports(member: &MemberExpr, bindings: &FxHashSet<Id>) -> bool {
+  matches!(&member.prop, MemberProp::Ident(prop) if prop.sym == "exports")
+    && is_cjs_global(&member.obj, "module", bindings)
+}
+
+/// Every id the module declares. After the resolver, a `module`/`exports` that is not
+/// among them is the unresolved CommonJS one.
+pub(crate) fn module_bindings(m: &Module) -> FxHashSet<Id> {
+  collect_decls::<Id, _>(m).into_iter().collect()
+}
+
+pub(crate) fn cjs_export(e: &Expr, bindings: &FxHashSet<Id>) -> Option<CjsExport> {
+  let Expr::Assign(AssignExpr {
+    op: op!("="),
+    left: AssignTarget::Simple(SimpleAssignTarget::Member(member)),
+    ..
+  }) = e
+  else {
+    return None;
+  };
+  if is_module_exports(member, bindings) {
+    return Some(CjsExport::Module);
+  }
+  let is_exports = is_cjs_global(&member.obj, "exports", bindings)
+    || matches!(&*member.obj, Expr::Member(obj) if is_module_exports(obj, bindings));
+  if !is_exports {
+    return None;
+  }
+  match &member.prop {
+    MemberProp::Ident(name) => Some(CjsExport::Named(name.sym.clone())),
+    MemberProp::Computed(ComputedPropName { expr, .. }) => match &**expr {
+      Expr::Lit(Lit::Str(name)) => Some(CjsExport::Named(name.value.clone())),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+// Name a property of `module.exports = { ... }` exports, `None` for spreads and computed keys
+pub(crate) fn cjs_prop_name(prop: &PropOrSpread) -> Option<Atom> {
+  let PropOrSpread::Prop(prop) = prop else {
+    return None;
+  };
+  let key = match &**prop {
+    Prop::Shorthand(ident) => return Some(ident.sym.clone()),
+    Prop::KeyValue(KeyValueProp { key, .. })
+    | Prop::Method(MethodProp { key, .. })
+    | Prop::Getter(GetterProp { key, .. })
+    | Prop::Setter(SetterProp { key, .. }) => key,
+    Prop::Assign(_) => return None,
+  };
+  match key {
+    PropName::Ident(ident) => Some(ident.sym.clone()),
+    PropName::Str(name) => Some(name.value.clone()),
+    _ => None,
+  }
+}
+
+pub fn keep_export(
+  exports: ExportSelector,
+  report: SharedTransformReport,
+) -> impl swc_core::ecma::ast::Pass {
   fold_pass(
     Repeat::new(KeepExportImpl {
       state: KeepExportState {
         keep_exports: exports,
+        report,
         ..Default::default()
       },
       in_lhs_of_var: false,
@@ -605,7 +766,7 @@ mod tests {
     let mut module = parse_js(input);
     let mut transform = Repeat::new(KeepExportImpl {
       state: KeepExportState {
-        keep_exports,
+        keep_exports: keep_exports.into(),
         ..Default::default()
       },
       in_lhs_of_var: false,
@@ -858,4 +1019,90 @@ export { getData };"#;
     
     test_transform(input, expected, vec!["getData".to_string()]);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_keep_export_patterns() {
+    let mut module = parse_js(
+      r#"export const getServerData = () => 1;
+export function getStaticData() { return 2; }
+export const getConfigTitle = () => "";
+export const unstable_settings = {};
+export const getData = () => 3;
+export default function Home() {}"#,
+    );
+    let exports: ExportSelector =
+      serde_json::from_str(r#"["get*Data", "/^unstable_/"]"#).unwrap();
+    module = module.fold_with(&mut Repeat::new(KeepExportImpl {
+      state: KeepExportState {
+        keep_exports: exports,
+        ..Default::default()
+      },
+      in_lhs_of_var: false,
+    }));
+
+    let output = emit_js(&module);
+    for kept in ["getServerData", "getStaticData", "unstable_settings", "getData"] {
+      assert!(output.contains(kept), "{output}");
+    }
+    assert!(!output.contains("getConfigTitle"), "{output}");
+    assert!(!output.contains("Home"), "{output}");
+  }
+
+  fn keep_cjs(input: &str, keep_exports: Vec<String>) -> String {
+    let module = parse_js(input).fold_with(&mut Repeat::new(KeepExportImpl {
+      state: KeepExportState {
+        keep_exports: keep_exports.into(),
+        ..Default::default()
+      },
+      in_lhs_of_var: false,
+    }));
+    emit_js(&module)
+  }
+
+  #[test]
+  fn test_keep_export_commonjs_assignments() {
+    let output = keep_cjs(
+      r#"const db = require('./db');
+const helper = require('./helper');
+exports.getData = () => db.query();
+module.exports.getConfig = function () { return helper(); };
+exports['getTitle'] = 'title';
+console.log('side effect');"#,
+      vec!["getConfig".to_string(), "getTitle".to_string()],
+    );
+
+    assert!(output.contains("module.exports.getConfig"), "{output}");
+    assert!(output.contains("require('./helper')"), "{output}");
+    assert!(output.contains("getTitle"), "{output}");
+    assert!(!output.contains("getData"), "{output}");
+    assert!(!output.contains("./db"), "{output}");
+    assert!(!output.contains("console"), "{output}");
+  }
+
+  #[test]
+  fn test_keep_export_commonjs_object() {
+    let output = keep_cjs(
+      r#"const db = require('./db');
+function getConfig() { return {}; }
+module.exports = {
+  getConfig,
+  getData: () => db.query(),
+  getTitle() { return 'title'; },
+};"#,
+      vec!["getConfig".to_string()],
+    );
+
+    assert!(output.contains("module.exports = {"), "{output}");
+    assert!(output.contains("function getConfig()"), "{output}");
+    assert!(!output.contains("getData"), "{output}");
+    assert!(!output.contains("getTitle"), "{output}");
+    assert!(!output.contains("./db"), "{output}");
+
+    let output = keep_cjs(
+      r#"function Page() {}
+module.exports = Page;"#,
+      vec!["getConfig".to_string()],
+    );
+    assert!(!output.contains("Page"), "{output}");
+  }
+}
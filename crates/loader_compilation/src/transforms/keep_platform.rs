@@ -1,7 +1,7 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, Deserialize, Serialize};
 use std::collections::HashMap;
 use swc_core::{
-  common::{SyntaxContext, DUMMY_SP},
+  common::{Span, SyntaxContext},
   ecma::{
     ast::*,
     atoms::Atom,
@@ -14,13 +14,38 @@ pub struct KeepPlatformPatcher {
   pub platform: String,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Debug)]
 #[serde(untagged)]
 pub enum KeepPlatformConfig {
   Bool(bool),
   KeepPlatform(String),
 }
 
+// Only `false` and known platforms, `true` or a typo would fold every flag to `false`
+// and drop the code each of them guards
+impl<'de> Deserialize<'de> for KeepPlatformConfig {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    let platform_map = get_platform_map();
+    match serde_json::Value::deserialize(deserializer)? {
+      serde_json::Value::Bool(false) => Ok(KeepPlatformConfig::Bool(false)),
+      serde_json::Value::String(platform) if platform_map.contains_key(&platform) => {
+        Ok(KeepPlatformConfig::KeepPlatform(platform))
+      }
+      value => {
+        let mut platforms = platform_map.into_keys().collect::<Vec<_>>();
+        platforms.sort();
+        Err(D::Error::custom(format!(
+          "Invalid `keepPlatform` {value}, expected `false` or one of: {}",
+          platforms.join(", ")
+        )))
+      }
+    }
+  }
+}
+
 impl Default for KeepPlatformConfig {
   fn default() -> Self {
     KeepPlatformConfig::Bool(false)
@@ -68,40 +93,49 @@ impl Fold for KeepPlatformPatcher {
       .unwrap_or_default();
     
     let mut new_module_items: Vec<ModuleItem> = vec![];
-    let mut env_variables: Vec<&Ident> = vec![];
+    // Local binding of each named flag import, with the flag it imports
+    let mut env_variables: Vec<(&Ident, String, Span)> = vec![];
     let mut decls: Vec<VarDeclarator> = vec![];
+    // Span of the first env import, where the flag declarations are inserted
+    let mut decls_span = None;
 
     for module_item in items.iter() {
       match module_item {
         ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl)) => {
           if check_source(&import_decl.src.value) {
+            decls_span.get_or_insert(import_decl.span);
             for specifier in import_decl.specifiers.iter() {
               match specifier {
                 ImportSpecifier::Named(named) => {
-                  let ImportNamedSpecifier { local, .. } = named;
-                  env_variables.push(local);
+                  let ImportNamedSpecifier { local, imported, span, .. } = named;
+                  let flag = match imported {
+                    Some(imported) => imported.atom().to_string(),
+                    None => local.sym.to_string(),
+                  };
+                  env_variables.push((local, flag, *span));
                 }
-                ImportSpecifier::Namespace(namespace) => {
-                  let ImportStarAsSpecifier { local, .. } = namespace;
+                // The default export is the same flags object as the namespace
+                ImportSpecifier::Namespace(ImportStarAsSpecifier { local, span })
+                | ImportSpecifier::Default(ImportDefaultSpecifier { local, span }) => {
                   decls.push(create_var_decl(
                     local.clone(),
                     Some(Box::new(Expr::Object(ObjectLit {
-                      span: DUMMY_SP,
+                      span: *span,
                       props: platform_flags
                         .iter()
                         .map(|platform| {
                           PropOrSpread::Prop(Box::new(Prop::KeyValue(
                             KeyValueProp {
-                              key: PropName::Ident(create_ident_name(platform)),
-                              value: Box::new(create_bool_expr(true)),
+                              key: PropName::Ident(create_ident_name(platform, *span)),
+                              value: Box::new(create_bool_expr(true, *span)),
                             },
                           )))
                         })
                         .collect(),
                     }))),
+                    *span,
                   ))
                 }
-                _ => {}
               }
             }
           } else {
@@ -114,18 +148,15 @@ impl Fold for KeepPlatformPatcher {
       }
     }
 
-    if !env_variables.is_empty() {
-      for env_variable in env_variables {
-        decls.push(create_var_decl(
-          env_variable.clone(),
-          Some(Box::new(create_bool_expr(
-            platform_flags.contains(&env_variable.sym.to_string()),
-          ))),
-        ));
-      }
+    for (env_variable, flag, span) in env_variables {
+      decls.push(create_var_decl(
+        env_variable.clone(),
+        Some(Box::new(create_bool_expr(platform_flags.contains(&flag), span))),
+        span,
+      ));
     }
 
-    insert_decls_into_module_items(decls, &mut new_module_items);
+    insert_decls_into_module_items(decls, &mut new_module_items, decls_span.unwrap_or_default());
     new_module_items
   }
 }
@@ -134,12 +165,16 @@ fn check_source(source: &str) -> bool {
   source == "universal-env" || source == "@uni/env"
 }
 
-fn insert_decls_into_module_items(decls: Vec<VarDeclarator>, module_items: &mut Vec<ModuleItem>) {
+fn insert_decls_into_module_items(
+  decls: Vec<VarDeclarator>,
+  module_items: &mut Vec<ModuleItem>,
+  span: Span,
+) {
   if !decls.is_empty() {
     module_items.insert(
       0,
       ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(VarDecl {
-        span: DUMMY_SP,
+        span,
         kind: VarDeclKind::Var,
         declare: false,
         decls,
@@ -149,14 +184,14 @@ fn insert_decls_into_module_items(decls: Vec<VarDeclarator>, module_items: &mut
   }
 }
 
-fn create_ident_name(value: &str) -> IdentName {
+fn create_ident_name(value: &str, span: Span) -> IdentName {
   IdentName {
-    span: DUMMY_SP,
+    span,
     sym: Atom::from(value)
   }
 }
 
-fn create_var_decl(id: Ident, init: Option<Box<Expr>>) -> VarDeclarator {
+fn create_var_decl(id: Ident, init: Option<Box<Expr>>, span: Span) -> VarDeclarator {
   let decl_name = Pat::Ident(BindingIdent {
     id,
     type_ann: Default::default(),
@@ -165,19 +200,18 @@ fn create_var_decl(id: Ident, init: Option<Box<Expr>>) -> VarDeclarator {
   VarDeclarator {
     name: decl_name,
     init,
-    span: DUMMY_SP,
+    span,
     definite: false,
   }
 }
 
-fn create_bool_expr(value: bool) -> Expr {
+fn create_bool_expr(value: bool, span: Span) -> Expr {
   Expr::Lit(Lit::Bool(Bool {
     value,
-    span: Default::default(),
+    span,
   }))
 }
 
-#[allow(dead_code)]
 pub fn keep_platform(options: KeepPlatformConfig) -> impl swc_core::ecma::ast::Pass {
   let platform: String = match options {
     KeepPlatformConfig::KeepPlatform(platform) => platform,
@@ -303,4 +337,77 @@ if (isWeChatMiniProgram) {
     
     test_transform(input, "wechat-miniprogram", vec![("isWeChatMiniProgram", true)]);
   }
+
+  // Initializer of the top-level `name` declaration
+  fn declared_init(module: &Module, name: &str) -> Expr {
+    module
+      .body
+      .iter()
+      .filter_map(|item| match item {
+        ModuleItem::Stmt(Stmt::Decl(Decl::Var(var))) => Some(&var.decls),
+        _ => None,
+      })
+      .flatten()
+      .find(|d| matches!(&d.name, Pat::Ident(id) if id.id.sym == name))
+      .and_then(|d| d.init.as_deref().cloned())
+      .unwrap_or_else(|| panic!("`{name}` is not declared"))
+  }
+
+  #[test]
+  fn test_keep_platform_config() {
+    let config: KeepPlatformConfig = serde_json::from_str(r#""kraken""#).unwrap();
+    assert!(matches!(config, KeepPlatformConfig::KeepPlatform(platform) if platform == "kraken"));
+    let config: KeepPlatformConfig = serde_json::from_str("false").unwrap();
+    assert!(matches!(config, KeepPlatformConfig::Bool(false)));
+
+    let error = serde_json::from_str::<KeepPlatformConfig>("true").unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "Invalid `keepPlatform` true, expected `false` or one of: baidu-smartprogram, \
+bytedance-microapp, kraken, kuaishou-miniprogram, miniapp, node, web, wechat-miniprogram, weex"
+    );
+    let error = serde_json::from_str::<KeepPlatformConfig>(r#""wechat""#).unwrap_err();
+    assert!(
+      error.to_string().starts_with(r#"Invalid `keepPlatform` "wechat", expected `false` or one of: "#),
+      "{error}"
+    );
+  }
+
+  fn keep_web(input: &str) -> Module {
+    parse_js(input).fold_with(&mut KeepPlatformPatcher {
+      platform: "web".to_string(),
+    })
+  }
+
+  #[test]
+  fn test_keep_platform_aliased_import() {
+    let module = keep_web(
+      r#"import { isWeb as w, isWeex as x } from 'universal-env';
+console.log(w, x);"#,
+    );
+
+    assert!(matches!(declared_init(&module, "w"), Expr::Lit(Lit::Bool(Bool { value: true, .. }))));
+    assert!(matches!(declared_init(&module, "x"), Expr::Lit(Lit::Bool(Bool { value: false, .. }))));
+  }
+
+  #[test]
+  fn test_keep_platform_default_import() {
+    let module = keep_web(
+      r#"import env, { isWeb } from 'universal-env';
+console.log(env.isWeb, isWeb);"#,
+    );
+
+    let Expr::Object(env) = declared_init(&module, "env") else {
+      panic!("`env` should be the flags object");
+    };
+    assert!(env.props.iter().any(|prop| matches!(
+      prop,
+      PropOrSpread::Prop(prop) if matches!(&**prop, Prop::KeyValue(KeyValueProp { key: PropName::Ident(key), .. }) if key.sym == "isWeb")
+    )));
+    assert!(matches!(declared_init(&module, "isWeb"), Expr::Lit(Lit::Bool(Bool { value: true, .. }))));
+    assert!(!module
+      .body
+      .iter()
+      .any(|item| matches!(item, ModuleItem::ModuleDecl(ModuleDecl::Import(_)))));
+  }
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 pub mod env_replacement;
 pub mod keep_export;
 pub mod remove_export;
+pub mod forbidden_imports;
 pub mod named_import_transform;
 pub mod change_package_import;
 pub mod keep_platform;
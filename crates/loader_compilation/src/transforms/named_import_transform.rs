@@ -1,79 +1,347 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use serde::Deserialize;
 use swc_core::{
   common::DUMMY_SP,
   ecma::{
     ast::*,
-    visit::{Fold, fold_pass},
+    visit::{Fold, FoldWith, Visit, VisitWith, fold_pass},
   },
 };
 
+use crate::options::{NameMatcher, NamePattern};
+
 pub struct TransformConfig {
-  pub packages: Vec<String>,
+  pub packages: PackageMatcher,
+  pub mixed_imports: bool,
+}
+
+/// Matches import sources against `namedImportTransform.packages`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "Vec<NamePattern>")]
+pub struct PackageMatcher(NameMatcher);
+
+impl PackageMatcher {
+  pub fn is_match(&self, src: &str) -> bool {
+    self.0.is_match(src)
+  }
+}
+
+impl TryFrom<Vec<NamePattern>> for PackageMatcher {
+  type Error = String;
+
+  fn try_from(packages: Vec<NamePattern>) -> Result<Self, Self::Error> {
+    NameMatcher::new(packages, "namedImportTransform.packages").map(Self)
+  }
 }
 
+// Plain package names in tests, where a `/regex/` that fails to compile is a bug in the test
+#[cfg(test)]
+impl From<Vec<String>> for PackageMatcher {
+  fn from(packages: Vec<String>) -> Self {
+    let packages = packages.into_iter().map(NamePattern::Name).collect::<Vec<_>>();
+    Self::try_from(packages).expect("package patterns should compile")
+  }
+}
+
+#[derive(Default)]
 pub struct NamedImportTransformImpl {
-  pub packages: Vec<String>,
+  pub packages: PackageMatcher,
+  // Also optimize imports with default or namespace specifiers
+  pub mixed_imports: bool,
+  // Local bound to each statically accessed member of the namespace imports being optimized
+  namespace_members: HashMap<Id, BTreeMap<String, Ident>>,
 }
 
-impl Fold for NamedImportTransformImpl {
-  fn fold_import_decl(&mut self, decl: ImportDecl) -> ImportDecl {
-    let src_value = decl.src.value.clone();
-    if self.packages.iter().any(|p| src_value == *p) {
-      let mut specifier_names = HashSet::new();
-      let mut skip = false;
-      for specifier in &decl.specifiers {
-        match specifier {
-          ImportSpecifier::Named(specifier) => {
-            if let Some(imported) = &specifier.imported {
-              match imported {
-                ModuleExportName::Ident(ident) => {
-                  specifier_names.insert(ident.sym.to_string());
-                }
-                ModuleExportName::Str(str) => {
-                  specifier_names.insert(str.value.to_string());
-                }
-              }
-            } else {
-              specifier_names.insert(specifier.local.sym.to_string());
+impl NamedImportTransformImpl {
+  fn new(packages: PackageMatcher, mixed_imports: bool) -> Self {
+    Self {
+      packages,
+      mixed_imports,
+      ..Default::default()
+    }
+  }
+
+  fn is_optimizable(&self, decl: &ImportDecl) -> bool {
+    !decl.type_only && self.packages.is_match(&decl.src.value)
+  }
+
+  // Namespace imports whose every use is `ns.foo`, so they can become named imports
+  fn collect_namespace_members(&mut self, items: &[ModuleItem]) {
+    let mut collector = NamespaceCollector::default();
+    for item in items {
+      if let ModuleItem::ModuleDecl(ModuleDecl::Import(decl)) = item {
+        if self.is_optimizable(decl) {
+          for specifier in &decl.specifiers {
+            if let ImportSpecifier::Namespace(namespace) = specifier {
+              collector
+                .namespaces
+                .insert(namespace.local.to_id(), Some(BTreeSet::new()));
             }
           }
-          ImportSpecifier::Default(_) => {
-            skip = true;
-          }
-          ImportSpecifier::Namespace(_) => {
-            skip = true;
+        }
+      }
+    }
+    if collector.namespaces.is_empty() {
+      return;
+    }
+    items.visit_with(&mut collector);
+
+    for ((sym, ctxt), members) in collector.namespaces {
+      let Some(members) = members.filter(|members| !members.is_empty()) else {
+        continue;
+      };
+      let members = members
+        .into_iter()
+        .map(|member| {
+          // Starts with `_` so it still names a component when used as a JSX tag
+          let local = Ident::new(format!("__{sym}_{member}__").into(), DUMMY_SP, ctxt);
+          (member, local)
+        })
+        .collect();
+      self.namespace_members.insert((sym, ctxt), members);
+    }
+  }
+
+  fn transform_import(&self, mut decl: ImportDecl) -> Vec<ModuleItem> {
+    let has_non_named = decl
+      .specifiers
+      .iter()
+      .any(|specifier| !matches!(specifier, ImportSpecifier::Named(_)));
+    if has_non_named && !self.mixed_imports {
+      return vec![decl.into()];
+    }
+
+    let mut names = BTreeSet::new();
+    let mut optimized = vec![];
+    let mut kept = vec![];
+    for specifier in std::mem::take(&mut decl.specifiers) {
+      match specifier {
+        ImportSpecifier::Named(named) => {
+          names.insert(imported_name(&named));
+          optimized.push(ImportSpecifier::Named(named));
+        }
+        ImportSpecifier::Namespace(namespace) => {
+          match self.namespace_members.get(&namespace.local.to_id()) {
+            Some(members) => {
+              for (member, local) in members {
+                names.insert(member.clone());
+                optimized.push(ImportSpecifier::Named(ImportNamedSpecifier {
+                  span: namespace.span,
+                  local: local.clone(),
+                  imported: Some(ModuleExportName::Ident(Ident::new_no_ctxt(
+                    member.as_str().into(),
+                    namespace.span,
+                  ))),
+                  is_type_only: false,
+                }));
+              }
+            }
+            None => kept.push(ImportSpecifier::Namespace(namespace)),
           }
         }
+        // Default exports are usually the barrel's own object, so they stay on it
+        default => kept.push(default),
       }
-      if !skip {
-        let mut names = specifier_names.into_iter().collect::<Vec<_>>();
-        names.sort();
-
-        let new_src = format!(
-          "__barrel_optimize__?names={}!=!{}?{}",
-          names.join(","),
-          src_value,
-          names.join(","),
-        );
-
-        let mut new_decl = decl.clone();
-        new_decl.src = Box::new(Str {
-          span: DUMMY_SP,
+    }
+
+    if optimized.is_empty() {
+      decl.specifiers = kept;
+      return vec![decl.into()];
+    }
+
+    let mut items = vec![];
+    if !kept.is_empty() {
+      items.push(
+        ImportDecl {
+          specifiers: kept,
+          ..decl.clone()
+        }
+        .into(),
+      );
+    }
+    let names = names.into_iter().collect::<Vec<_>>().join(",");
+    let new_src = format!(
+      "__barrel_optimize__?names={names}!=!{}?{names}",
+      decl.src.value,
+    );
+    items.push(
+      ImportDecl {
+        specifiers: optimized,
+        src: Box::new(Str {
+          span: decl.src.span,
           value: new_src.into(),
           raw: None,
-        });
+        }),
+        ..decl
+      }
+      .into(),
+    );
+    items
+  }
+
+  fn namespace_member(&self, obj: &Ident, member: Option<String>) -> Option<Ident> {
+    self
+      .namespace_members
+      .get(&obj.to_id())?
+      .get(&member?)
+      .cloned()
+  }
+}
+
+impl Fold for NamedImportTransformImpl {
+  fn fold_module_items(&mut self, items: Vec<ModuleItem>) -> Vec<ModuleItem> {
+    if self.mixed_imports {
+      self.collect_namespace_members(&items);
+    }
+
+    let mut new_items = Vec::with_capacity(items.len());
+    for item in items {
+      match item {
+        ModuleItem::ModuleDecl(ModuleDecl::Import(decl)) if self.is_optimizable(&decl) => {
+          new_items.extend(self.transform_import(decl));
+        }
+        item => new_items.push(item.fold_with(self)),
+      }
+    }
+    new_items
+  }
 
-        return new_decl;
+  fn fold_expr(&mut self, expr: Expr) -> Expr {
+    if let Expr::Member(member) = &expr {
+      if let Expr::Ident(obj) = &*member.obj {
+        if let Some(local) = self.namespace_member(obj, static_member(&member.prop)) {
+          return Expr::Ident(Ident {
+            span: member.span,
+            ..local
+          });
+        }
       }
     }
-    decl
+    expr.fold_children_with(self)
+  }
+
+  fn fold_jsx_element_name(&mut self, name: JSXElementName) -> JSXElementName {
+    if let JSXElementName::JSXMemberExpr(member) = &name {
+      if let JSXObject::Ident(obj) = &member.obj {
+        if let Some(local) = self.namespace_member(obj, Some(member.prop.sym.to_string())) {
+          return JSXElementName::Ident(Ident {
+            span: member.span,
+            ..local
+          });
+        }
+      }
+    }
+    name.fold_children_with(self)
+  }
+
+  fn fold_jsx_object(&mut self, object: JSXObject) -> JSXObject {
+    if let JSXObject::JSXMemberExpr(member) = &object {
+      if let JSXObject::Ident(obj) = &member.obj {
+        if let Some(local) = self.namespace_member(obj, Some(member.prop.sym.to_string())) {
+          return JSXObject::Ident(Ident {
+            span: member.span,
+            ..local
+          });
+        }
+      }
+    }
+    object.fold_children_with(self)
+  }
+}
+
+fn imported_name(specifier: &ImportNamedSpecifier) -> String {
+  match &specifier.imported {
+    Some(ModuleExportName::Ident(ident)) => ident.sym.to_string(),
+    Some(ModuleExportName::Str(str)) => str.value.to_string(),
+    None => specifier.local.sym.to_string(),
+  }
+}
+
+// `ns.foo` or `ns["foo"]`, as long as `foo` can be imported under an identifier
+fn static_member(prop: &MemberProp) -> Option<String> {
+  match prop {
+    MemberProp::Ident(ident) => Some(ident.sym.to_string()),
+    MemberProp::Computed(ComputedPropName { expr, .. }) => match &**expr {
+      Expr::Lit(Lit::Str(str)) if Ident::verify_symbol(&str.value).is_ok() => {
+        Some(str.value.to_string())
+      }
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+// Members read from each namespace import, `None` once it is used as a value
+#[derive(Default)]
+struct NamespaceCollector {
+  namespaces: HashMap<Id, Option<BTreeSet<String>>>,
+}
+
+impl NamespaceCollector {
+  fn record(&mut self, obj: &Ident, member: Option<String>) -> bool {
+    let Some(usage) = self.namespaces.get_mut(&obj.to_id()) else {
+      return false;
+    };
+    match (usage.as_mut(), member) {
+      (Some(members), Some(member)) => {
+        members.insert(member);
+      }
+      _ => *usage = None,
+    }
+    true
+  }
+}
+
+impl Visit for NamespaceCollector {
+  fn visit_import_decl(&mut self, _: &ImportDecl) {}
+
+  fn visit_ident(&mut self, ident: &Ident) {
+    if let Some(usage) = self.namespaces.get_mut(&ident.to_id()) {
+      *usage = None;
+    }
+  }
+
+  fn visit_member_expr(&mut self, member: &MemberExpr) {
+    if let Expr::Ident(obj) = &*member.obj {
+      if self.record(obj, static_member(&member.prop)) {
+        member.prop.visit_with(self);
+        return;
+      }
+    }
+    member.visit_children_with(self);
+  }
+
+  // `ns?.foo` and `ns.foo = x` are left alone
+  fn visit_opt_chain_base(&mut self, base: &OptChainBase) {
+    if let OptChainBase::Member(MemberExpr { obj, .. }) = base {
+      if let Expr::Ident(obj) = &**obj {
+        self.record(obj, None);
+      }
+    }
+    base.visit_children_with(self);
+  }
+
+  fn visit_simple_assign_target(&mut self, target: &SimpleAssignTarget) {
+    if let SimpleAssignTarget::Member(MemberExpr { obj, .. }) = target {
+      if let Expr::Ident(obj) = &**obj {
+        self.record(obj, None);
+      }
+    }
+    target.visit_children_with(self);
+  }
+
+  fn visit_jsx_member_expr(&mut self, member: &JSXMemberExpr) {
+    if let JSXObject::Ident(obj) = &member.obj {
+      if self.record(obj, Some(member.prop.sym.to_string())) {
+        return;
+      }
+    }
+    member.visit_children_with(self);
   }
 }
 
 pub fn named_import_transform(config: TransformConfig) -> impl swc_core::ecma::ast::Pass {
-  fold_pass(NamedImportTransformImpl {
-    packages: config.packages,
-  })
+  fold_pass(NamedImportTransformImpl::new(config.packages, config.mixed_imports))
 }
 
 #[cfg(test)]
@@ -82,7 +350,8 @@ mod tests {
   use swc_core::{
     common::{FileName, SourceMap},
     ecma::{
-      parser::{lexer::Lexer, Parser, StringInput, Syntax},
+      codegen::{text_writer::JsWriter, Emitter},
+      parser::{lexer::Lexer, EsSyntax, Parser, StringInput, Syntax},
       visit::FoldWith,
     },
   };
@@ -91,7 +360,10 @@ mod tests {
     let cm = SourceMap::default();
     let fm = cm.new_source_file(FileName::Anon.into(), code.to_string());
     let lexer = Lexer::new(
-      Syntax::Es(Default::default()),
+      Syntax::Es(EsSyntax {
+        jsx: true,
+        ..Default::default()
+      }),
       Default::default(),
       StringInput::from(&*fm),
       None,
@@ -100,13 +372,37 @@ mod tests {
     parser.parse_module().expect("Failed to parse module")
   }
 
+  fn emit_js(module: &Module) -> String {
+    let mut buf = vec![];
+    {
+      let writer = JsWriter::new(SourceMap::default().into(), "\n", &mut buf, None);
+      let mut emitter = Emitter {
+        cfg: Default::default(),
+        comments: None,
+        cm: SourceMap::default().into(),
+        wr: writer,
+      };
+      emitter.emit_module(module).unwrap();
+    }
+    String::from_utf8(buf).unwrap()
+  }
+
+  fn transform_mixed(code: &str) -> String {
+    let module = parse_js(code).fold_with(&mut NamedImportTransformImpl::new(
+      vec!["antd".to_string()].into(),
+      true,
+    ));
+    emit_js(&module)
+  }
+
   #[test]
   fn test_named_import_transform_basic() {
     let code = r#"import { Button, Input } from 'antd';"#;
     
     let mut module = parse_js(code);
     let mut transform = NamedImportTransformImpl {
-      packages: vec!["antd".to_string()],
+      packages: vec!["antd".to_string()].into(),
+      ..Default::default()
     };
     module = module.fold_with(&mut transform);
     
@@ -124,7 +420,8 @@ mod tests {
     
     let mut module = parse_js(code);
     let mut transform = NamedImportTransformImpl {
-      packages: vec!["react".to_string()],
+      packages: vec!["react".to_string()].into(),
+      ..Default::default()
     };
     module = module.fold_with(&mut transform);
     
@@ -141,7 +438,8 @@ mod tests {
     
     let mut module = parse_js(code);
     let mut transform = NamedImportTransformImpl {
-      packages: vec!["react".to_string()],
+      packages: vec!["react".to_string()].into(),
+      ..Default::default()
     };
     module = module.fold_with(&mut transform);
     
@@ -158,7 +456,8 @@ mod tests {
     
     let mut module = parse_js(code);
     let mut transform = NamedImportTransformImpl {
-      packages: vec!["antd".to_string()],
+      packages: vec!["antd".to_string()].into(),
+      ..Default::default()
     };
     module = module.fold_with(&mut transform);
     
@@ -175,7 +474,8 @@ mod tests {
     
     let mut module = parse_js(code);
     let mut transform = NamedImportTransformImpl {
-      packages: vec!["antd".to_string()],
+      packages: vec!["antd".to_string()].into(),
+      ..Default::default()
     };
     module = module.fold_with(&mut transform);
     
@@ -193,7 +493,8 @@ mod tests {
     
     let mut module = parse_js(code);
     let mut transform = NamedImportTransformImpl {
-      packages: vec!["antd".to_string()],
+      packages: vec!["antd".to_string()].into(),
+      ..Default::default()
     };
     module = module.fold_with(&mut transform);
     
@@ -203,4 +504,86 @@ mod tests {
       assert!(import_decl.src.value.contains("Button,Form,Input"));
     }
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_named_import_transform_package_patterns() {
+    let packages: PackageMatcher = serde_json::from_str(
+      r#"["antd", "@ali/ui-*", { "glob": "lodash-es/**" }, { "regex": "^@alifd/next(/|$)" }]"#,
+    )
+    .unwrap();
+
+    for src in ["antd", "@ali/ui-button", "lodash-es/index.js", "@alifd/next", "@alifd/next/lib"] {
+      let mut module = parse_js(&format!("import {{ a }} from '{src}';"));
+      module = module.fold_with(&mut NamedImportTransformImpl {
+        packages: packages.clone(),
+        ..Default::default()
+      });
+      if let ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl)) = &module.body[0] {
+        assert!(import_decl.src.value.contains("__barrel_optimize__"), "{src}");
+      }
+    }
+    for src in ["antd-mobile", "@ali/ui-button/es", "@ali/other", "@alifd/next-theme"] {
+      assert!(!packages.is_match(src), "{src}");
+    }
+  }
+
+  #[test]
+  fn test_named_import_transform_invalid_regex() {
+    let error = serde_json::from_str::<PackageMatcher>(r#"[{ "regex": "(" }]"#).unwrap_err();
+    assert!(error.to_string().contains("namedImportTransform.packages"), "{error}");
+    let error = serde_json::from_str::<PackageMatcher>(r#"["/(/"]"#).unwrap_err();
+    assert!(error.to_string().contains("namedImportTransform.packages"), "{error}");
+  }
+
+  #[test]
+  fn test_named_import_transform_split_default() {
+    let output = transform_mixed("import Antd, { Button, Input as I } from 'antd';");
+    assert!(output.contains(r#"import Antd from 'antd';"#), "{output}");
+    assert!(
+      output.contains(r#"import { Button, Input as I } from "__barrel_optimize__?names=Button,Input!=!antd?Button,Input";"#),
+      "{output}"
+    );
+  }
+
+  #[test]
+  fn test_named_import_transform_static_namespace() {
+    let output = transform_mixed(
+      r#"import * as antd from 'antd';
+const button = antd.Button;
+antd['message'].info('hi');
+const el = <antd.Form.Item />;"#,
+    );
+    assert!(
+      output.contains(r#"import { Button as __antd_Button__, Form as __antd_Form__, message as __antd_message__ } from "__barrel_optimize__?names=Button,Form,message!=!antd?Button,Form,message";"#),
+      "{output}"
+    );
+    assert!(output.contains("const button = __antd_Button__;"), "{output}");
+    assert!(output.contains("__antd_message__.info('hi');"), "{output}");
+    assert!(output.contains("<__antd_Form__.Item/>"), "{output}");
+    assert!(!output.contains("antd."), "{output}");
+  }
+
+  #[test]
+  fn test_named_import_transform_escaping_namespace() {
+    for code in [
+      "import * as antd from 'antd'; console.log(antd.Button, antd);",
+      "import * as antd from 'antd'; antd[key]();",
+      "import * as antd from 'antd'; antd?.Button;",
+      "import * as antd from 'antd'; antd.Button; export { antd };",
+    ] {
+      let output = transform_mixed(code);
+      assert!(output.contains("import * as antd from 'antd';"), "{output}");
+      assert!(!output.contains("__barrel_optimize__"), "{output}");
+    }
+  }
+
+  #[test]
+  fn test_named_import_transform_mixed_disabled() {
+    let mut module = parse_js("import * as antd from 'antd'; antd.Button;");
+    module = module.fold_with(&mut NamedImportTransformImpl {
+      packages: vec!["antd".to_string()].into(),
+      ..Default::default()
+    });
+    assert!(!emit_js(&module).contains("__barrel_optimize__"));
+  }
+}
@@ -1,5 +1,6 @@
+use serde::Deserialize;
 use swc_core::{
-  common::{util::take::Take, SyntaxContext, DUMMY_SP},
+  common::{util::take::Take, Span, Spanned, SyntaxContext},
   ecma::{
     ast::*,
     atoms::Atom,
@@ -7,16 +8,57 @@ use swc_core::{
   },
 };
 
-pub struct NodeTransform;
+/// Names of the runtime helpers the transformed module is evaluated with.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct NodeTransformRuntime {
+  pub import: String,
+  pub dynamic_import: String,
+  pub import_meta: String,
+  pub exports: String,
+  pub exports_all: String,
+}
+
+impl Default for NodeTransformRuntime {
+  fn default() -> Self {
+    Self {
+      import: "__ice_import__".to_string(),
+      dynamic_import: "__ice_dynamic_import__".to_string(),
+      import_meta: "__ice_import_meta__".to_string(),
+      exports: "__ice_exports__".to_string(),
+      exports_all: "__ice_exports_all__".to_string(),
+    }
+  }
+}
+
+#[derive(Clone, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum NodeTransformConfig {
+  Bool(bool),
+  Runtime(NodeTransformRuntime),
+}
 
-fn create_import_str(key: i32) -> String {
-  format!("__ice_import_{key}__")
+impl Default for NodeTransformConfig {
+  fn default() -> Self {
+    NodeTransformConfig::Bool(false)
+  }
 }
 
-fn create_var_decl(id: &str, init: Option<Box<Expr>>) -> VarDeclarator {
+#[derive(Default)]
+pub struct NodeTransform {
+  pub runtime: NodeTransformRuntime,
+}
+
+// Local binding for the `key`th import, derived from the import helper name,
+// e.g. `__ice_import__` becomes `__ice_import_0__`.
+fn create_import_str(import_helper: &str, key: i32) -> String {
+  format!("{}_{key}__", import_helper.trim_end_matches('_'))
+}
+
+fn create_var_decl(id: &str, init: Option<Box<Expr>>, span: Span) -> VarDeclarator {
   let decl_name: Pat = Pat::Ident(BindingIdent {
     id: Ident {
-      span: DUMMY_SP,
+      span,
       sym: Atom::from(id),
       optional: Default::default(),
       ctxt: SyntaxContext::empty(),
@@ -24,30 +66,30 @@ fn create_var_decl(id: &str, init: Option<Box<Expr>>) -> VarDeclarator {
     type_ann: Default::default(),
   });
   VarDeclarator {
-    span: DUMMY_SP,
+    span,
     name: decl_name,
     init,
     definite: false,
   }
 }
 
-fn create_member_decl(id: Ident, object_name: &str, property: &str) -> VarDeclarator {
+fn create_member_decl(id: Ident, object_name: &str, property: &str, span: Span) -> VarDeclarator {
   VarDeclarator {
-    span: DUMMY_SP,
+    span,
     name: Pat::Ident(BindingIdent {
       id,
       type_ann: Default::default(),
     }),
     init: Some(Box::new(Expr::Member(MemberExpr {
-      span: DUMMY_SP,
+      span,
       obj: Box::new(Expr::Ident(Ident {
-        span: DUMMY_SP,
+        span,
         sym: Atom::from(object_name),
         optional: Default::default(),
         ctxt: SyntaxContext::empty(),
       })),
       prop: MemberProp::Ident(IdentName {
-        span: DUMMY_SP,
+        span,
         sym: Atom::from(property),
       }),
     }))),
@@ -55,11 +97,16 @@ fn create_member_decl(id: Ident, object_name: &str, property: &str) -> VarDeclar
   }
 }
 
-fn create_import_decl(import_val: &str, import_source: &str) -> ModuleItem {
+fn create_import_decl(
+  import_helper: &str,
+  import_val: &str,
+  import_source: &str,
+  span: Span,
+) -> ModuleItem {
   let call_args = vec![ExprOrSpread {
     spread: Take::dummy(),
     expr: Box::new(Expr::Lit(Lit::Str(Str {
-      span: DUMMY_SP,
+      span,
       value: Atom::from(import_source),
       raw: Default::default(),
     }))),
@@ -67,13 +114,13 @@ fn create_import_decl(import_val: &str, import_source: &str) -> ModuleItem {
   let decls: Vec<VarDeclarator> = vec![create_var_decl(
     import_val,
     Some(Box::new(Expr::Await(AwaitExpr {
-      span: DUMMY_SP,
+      span,
       arg: Box::new(Expr::Call(CallExpr {
-        span: DUMMY_SP,
+        span,
         callee: Callee::Expr(Box::new(Expr::Ident(Ident {
           ctxt: SyntaxContext::empty(),
-          span: DUMMY_SP,
-          sym: Atom::from("__ice_import__"),
+          span,
+          sym: Atom::from(import_helper),
           optional: Default::default(),
         }))),
         args: call_args,
@@ -81,10 +128,11 @@ fn create_import_decl(import_val: &str, import_source: &str) -> ModuleItem {
         ctxt: SyntaxContext::empty(),
       })),
     }))),
+    span,
   )];
 
   ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(VarDecl {
-    span: DUMMY_SP,
+    span,
     kind: VarDeclKind::Const,
     declare: false,
     decls,
@@ -92,22 +140,22 @@ fn create_import_decl(import_val: &str, import_source: &str) -> ModuleItem {
   }))))
 }
 
-fn create_define_export(name: &str, value: &str) -> ModuleItem {
+fn create_define_export(exports_helper: &str, name: &str, value: &str, span: Span) -> ModuleItem {
   ModuleItem::Stmt(Stmt::Expr(ExprStmt {
-    span: DUMMY_SP,
+    span,
     expr: Box::new(Expr::Call(CallExpr {
       ctxt: SyntaxContext::empty(),
-      span: DUMMY_SP,
+      span,
       callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
-        span: DUMMY_SP,
+        span,
         obj: Box::new(Expr::Ident(Ident {
           ctxt: SyntaxContext::empty(),
-          span: DUMMY_SP,
+          span,
           sym: Atom::from("Object"),
           optional: Default::default(),
         })),
         prop: MemberProp::Ident(IdentName {
-          span: DUMMY_SP,
+          span,
           sym: Atom::from("defineProperty"),
         }),
       }))),
@@ -115,8 +163,8 @@ fn create_define_export(name: &str, value: &str) -> ModuleItem {
         ExprOrSpread {
           spread: Take::dummy(),
           expr: Box::new(Expr::Ident(Ident {
-            span: DUMMY_SP,
-            sym: Atom::from("__ice_exports__"),
+            span,
+            sym: Atom::from(exports_helper),
             optional: Default::default(),
             ctxt: SyntaxContext::empty(),
           })),
@@ -124,7 +172,7 @@ fn create_define_export(name: &str, value: &str) -> ModuleItem {
         ExprOrSpread {
           spread: Take::dummy(),
           expr: Box::new(Expr::Lit(Lit::Str(Str {
-            span: DUMMY_SP,
+            span,
             value: Atom::from(name),
             raw: Default::default(),
           }))),
@@ -132,38 +180,39 @@ fn create_define_export(name: &str, value: &str) -> ModuleItem {
         ExprOrSpread {
           spread: Take::dummy(),
           expr: Box::new(Expr::Object(ObjectLit {
-            span: DUMMY_SP,
+            span,
             props: vec![
               PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
                 key: PropName::Ident(IdentName {
-                  span: DUMMY_SP,
+                  span,
                   sym: Atom::from("enumerable"),
                 }),
                 value: Box::new(Expr::Lit(Lit::Bool(Bool {
-                  span: DUMMY_SP,
+                  span,
                   value: true,
                 }))),
               }))),
-              PropOrSpread::Prop(Box::new(Prop::Getter(GetterProp {
-                span: DUMMY_SP,
+              PropOrSpread::Prop(Box::new(Prop::Method(MethodProp {
                 key: PropName::Ident(IdentName {
-                  span: DUMMY_SP,
+                  span,
                   sym: Atom::from("get"),
                 }),
-                body: Some(BlockStmt {
-                  ctxt: SyntaxContext::empty(),
-                  span: DUMMY_SP,
-                  stmts: vec![Stmt::Return(ReturnStmt {
-                    span: DUMMY_SP,
-                    arg: Some(Box::new(Expr::Ident(Ident {
-                      ctxt: SyntaxContext::empty(),
-                      span: DUMMY_SP,
-                      sym: Atom::from(value),
-                      optional: Default::default(),
-                    }))),
-                  })],
+                function: Box::new(Function {
+                  body: Some(BlockStmt {
+                    ctxt: SyntaxContext::empty(),
+                    span,
+                    stmts: vec![Stmt::Return(ReturnStmt {
+                      span,
+                      arg: Some(Box::new(Expr::Ident(Ident {
+                        ctxt: SyntaxContext::empty(),
+                        span,
+                        sym: Atom::from(value),
+                        optional: Default::default(),
+                      }))),
+                    })],
+                  }),
+                  ..Default::default()
                 }),
-                type_ann: Default::default(),
               }))),
             ],
           })),
@@ -174,22 +223,22 @@ fn create_define_export(name: &str, value: &str) -> ModuleItem {
   }))
 }
 
-fn create_call_expr(name: &str) -> ModuleItem {
+fn create_call_expr(exports_all_helper: &str, name: &str, span: Span) -> ModuleItem {
   ModuleItem::Stmt(Stmt::Expr(ExprStmt {
-    span: DUMMY_SP,
+    span,
     expr: Box::new(Expr::Call(CallExpr {
-      span: DUMMY_SP,
+      span,
       callee: Callee::Expr(Box::new(Expr::Ident(Ident {
         ctxt: SyntaxContext::empty(),
-        span: DUMMY_SP,
-        sym: Atom::from("__ice_exports_all__"),
+        span,
+        sym: Atom::from(exports_all_helper),
         optional: Default::default(),
       }))),
       args: vec![ExprOrSpread {
         spread: Take::dummy(),
         expr: Box::new(Expr::Ident(Ident {
           ctxt: SyntaxContext::empty(),
-          span: DUMMY_SP,
+          span,
           sym: Atom::from(name),
           optional: Default::default(),
         })),
@@ -200,21 +249,21 @@ fn create_call_expr(name: &str) -> ModuleItem {
   }))
 }
 
-fn create_default_export(right: Box<Expr>) -> ModuleItem {
+fn create_default_export(exports_helper: &str, right: Box<Expr>, span: Span) -> ModuleItem {
   ModuleItem::Stmt(Stmt::Expr(ExprStmt {
-    span: DUMMY_SP,
+    span,
     expr: Box::new(Expr::Assign(AssignExpr {
-      span: DUMMY_SP,
+      span,
       left: AssignTarget::Simple(SimpleAssignTarget::Member(MemberExpr {
-        span: DUMMY_SP,
+        span,
         obj: Box::new(Expr::Ident(Ident {
-          span: DUMMY_SP,
-          sym: Atom::from("__ice_exports__"),
+          span,
+          sym: Atom::from(exports_helper),
           optional: false,
           ctxt: SyntaxContext::empty(),
         })),
         prop: MemberProp::Ident(IdentName {
-          span: DUMMY_SP,
+          span,
           sym: Atom::from("default"),
         }),
       })),
@@ -235,45 +284,56 @@ impl Fold for NodeTransform {
   fn fold_module_items(&mut self, items: Vec<ModuleItem>) -> Vec<ModuleItem> {
     let mut new_module_items: Vec<ModuleItem> = vec![];
     let mut import_id: i32 = 0;
+    let NodeTransformRuntime {
+      import,
+      exports,
+      exports_all,
+      ..
+    } = self.runtime.clone();
     for module_item in items.iter() {
       match module_item {
         ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl)) => {
-          let import_val = format!("__ice_import_{import_id}__");
+          let import_val = create_import_str(&import, import_id);
           import_id += 1;
-          new_module_items.push(create_import_decl(&import_val, &import_decl.src.value));
+          new_module_items.push(create_import_decl(
+            &import,
+            &import_val,
+            &import_decl.src.value,
+            import_decl.span,
+          ));
 
           for specifier in import_decl.specifiers.iter() {
             match specifier {
               ImportSpecifier::Named(named) => {
-                let ImportNamedSpecifier { local, imported, .. } = named;
+                let ImportNamedSpecifier { local, imported, span, .. } = named;
                 let mut property = &local.sym;
                 if let Some(ModuleExportName::Ident(import_ident)) = imported {
                   property = &import_ident.sym;
                 }
                 new_module_items.push(ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(VarDecl {
-                  span: DUMMY_SP,
+                  span: *span,
                   kind: VarDeclKind::Const,
                   declare: false,
-                  decls: vec![create_member_decl(local.clone(), &import_val, property)],
+                  decls: vec![create_member_decl(local.clone(), &import_val, property, *span)],
                   ctxt: SyntaxContext::empty(),
                 })))))
               }
               ImportSpecifier::Namespace(namespace) => {
-                let ImportStarAsSpecifier { local, .. } = namespace;
+                let ImportStarAsSpecifier { local, span } = namespace;
                 new_module_items.push(ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(VarDecl {
                   ctxt: SyntaxContext::empty(),
-                  span: DUMMY_SP,
+                  span: *span,
                   kind: VarDeclKind::Const,
                   declare: false,
                   decls: vec![VarDeclarator {
-                    span: DUMMY_SP,
+                    span: *span,
                     name: Pat::Ident(BindingIdent {
                       id: local.clone(),
                       type_ann: Default::default(),
                     }),
                     init: Some(Box::new(Expr::Ident(Ident {
                       ctxt: SyntaxContext::empty(),
-                      span: DUMMY_SP,
+                      span: *span,
                       sym: Atom::from(import_val.clone()),
                       optional: Default::default(),
                     }))),
@@ -282,12 +342,12 @@ impl Fold for NodeTransform {
                 })))))
               }
               ImportSpecifier::Default(default) => {
-                let ImportDefaultSpecifier { local, .. } = default;
+                let ImportDefaultSpecifier { local, span } = default;
                 new_module_items.push(ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(VarDecl {
-                  span: DUMMY_SP,
+                  span: *span,
                   kind: VarDeclKind::Const,
                   declare: false,
-                  decls: vec![create_member_decl(local.clone(), &import_val, "default")],
+                  decls: vec![create_member_decl(local.clone(), &import_val, "default", *span)],
                   ctxt: SyntaxContext::empty(),
                 })))))
               }
@@ -295,18 +355,18 @@ impl Fold for NodeTransform {
           }
         }
         ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(export_named)) => {
-          let import_val = create_import_str(import_id);
+          let import_val = create_import_str(&import, import_id);
           let mut has_import = false;
           if let Some(src) = &export_named.src {
             has_import = true;
             import_id += 1;
-            new_module_items.push(create_import_decl(&import_val, &src.value));
+            new_module_items.push(create_import_decl(&import, &import_val, &src.value, export_named.span));
           }
 
           for specifier in export_named.specifiers.iter() {
             match specifier {
               ExportSpecifier::Named(named) => {
-                let ExportNamedSpecifier { orig, exported, .. } = named;
+                let ExportNamedSpecifier { orig, exported, span, .. } = named;
                 let orig_name = get_module_name(orig);
                 let export_name = if let Some(exported_ident) = exported {
                   get_module_name(exported_ident)
@@ -318,15 +378,15 @@ impl Fold for NodeTransform {
                 } else {
                   orig_name.to_string()
                 };
-                new_module_items.push(create_define_export(export_name, &return_value));
+                new_module_items.push(create_define_export(&exports, export_name, &return_value, *span));
               }
               ExportSpecifier::Namespace(namespace) => {
-                let ExportNamespaceSpecifier { name, .. } = namespace;
+                let ExportNamespaceSpecifier { name, span } = namespace;
                 let export_name = get_module_name(name);
                 if has_import {
-                  new_module_items.push(create_define_export(export_name, &import_val));
+                  new_module_items.push(create_define_export(&exports, export_name, &import_val, *span));
                 } else {
-                  new_module_items.push(create_define_export(export_name, export_name));
+                  new_module_items.push(create_define_export(&exports, export_name, export_name, *span));
                 }
               }
               _ => {}
@@ -343,9 +403,18 @@ impl Fold for NodeTransform {
                   declare: false,
                   class: class_decl.class.clone(),
                 }))));
-                new_module_items.push(create_define_export("default", export_name));
+                new_module_items.push(create_define_export(
+                  &exports,
+                  "default",
+                  export_name,
+                  export_default_decl.span,
+                ));
               } else {
-                new_module_items.push(create_default_export(Box::new(Expr::Class(class_decl.clone()))));
+                new_module_items.push(create_default_export(
+                  &exports,
+                  Box::new(Expr::Class(class_decl.clone())),
+                  export_default_decl.span,
+                ));
               }
             }
             DefaultDecl::Fn(function_decl) => {
@@ -356,9 +425,18 @@ impl Fold for NodeTransform {
                   function: function_decl.function.clone(),
                   declare: false,
                 }))));
-                new_module_items.push(create_define_export("default", export_name));
+                new_module_items.push(create_define_export(
+                  &exports,
+                  "default",
+                  export_name,
+                  export_default_decl.span,
+                ));
               } else {
-                new_module_items.push(create_default_export(Box::new(Expr::Fn(function_decl.clone()))));
+                new_module_items.push(create_default_export(
+                  &exports,
+                  Box::new(Expr::Fn(function_decl.clone())),
+                  export_default_decl.span,
+                ));
               }
             }
             _ => {
@@ -367,32 +445,41 @@ impl Fold for NodeTransform {
           }
         }
         ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(export_default_expr)) => {
-          new_module_items.push(create_default_export(export_default_expr.expr.clone()));
+          new_module_items.push(create_default_export(
+            &exports,
+            export_default_expr.expr.clone(),
+            export_default_expr.span,
+          ));
         }
         ModuleItem::ModuleDecl(ModuleDecl::ExportAll(export_all)) => {
-          let import_val = create_import_str(import_id);
+          let import_val = create_import_str(&import, import_id);
           import_id += 1;
-          new_module_items.push(create_import_decl(&import_val, &export_all.src.value));
-          new_module_items.push(create_call_expr(&import_val));
+          new_module_items.push(create_import_decl(
+            &import,
+            &import_val,
+            &export_all.src.value,
+            export_all.span,
+          ));
+          new_module_items.push(create_call_expr(&exports_all, &import_val, export_all.span));
         }
         ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export_decl)) => {
           match &export_decl.decl {
             Decl::Class(class_decl) => {
               let class_name = &class_decl.ident.sym;
               new_module_items.push(ModuleItem::Stmt(Stmt::Decl(Decl::Class(class_decl.clone()))));
-              new_module_items.push(create_define_export(class_name, class_name));
+              new_module_items.push(create_define_export(&exports, class_name, class_name, export_decl.span));
             }
             Decl::Fn(fn_decl) => {
               let fn_name = &fn_decl.ident.sym;
               new_module_items.push(ModuleItem::Stmt(Stmt::Decl(Decl::Fn(fn_decl.clone()))));
-              new_module_items.push(create_define_export(fn_name, fn_name));
+              new_module_items.push(create_define_export(&exports, fn_name, fn_name, export_decl.span));
             }
             Decl::Var(var_decl) => {
               new_module_items.push(ModuleItem::Stmt(Stmt::Decl(Decl::Var(var_decl.clone()))));
               for decl in var_decl.decls.iter() {
                 if decl.name.is_ident() {
                   let var_name = &decl.name.as_ident().unwrap().id.sym;
-                  new_module_items.push(create_define_export(var_name, var_name));
+                  new_module_items.push(create_define_export(&exports, var_name, var_name, decl.span));
                 }
               }
             }
@@ -410,17 +497,17 @@ impl Fold for NodeTransform {
 
   fn fold_call_expr(&mut self, call_expr: CallExpr) -> CallExpr {
     let callee = &call_expr.callee;
-    if let Callee::Import(_) = callee {
+    if let Callee::Import(import) = callee {
       CallExpr {
         span: call_expr.span,
-        args: call_expr.args,
-        type_args: call_expr.type_args,
         callee: Callee::Expr(Box::new(Expr::Ident(Ident {
-          span: DUMMY_SP,
-          sym: Atom::from("__ice_dynamic_import__"),
+          span: import.span,
+          sym: Atom::from(self.runtime.dynamic_import.as_str()),
           optional: Default::default(),
           ctxt: SyntaxContext::empty(),
         }))),
+        args: call_expr.args,
+        type_args: call_expr.type_args,
         ctxt: SyntaxContext::empty(),
       }
     } else {
@@ -435,8 +522,8 @@ impl Fold for NodeTransform {
       MemberExpr {
         span: member_expr.span,
         obj: Box::new(Expr::Ident(Ident {
-          span: DUMMY_SP,
-          sym: Atom::from("__ice_import_meta__"),
+          span: member_expr.obj.span(),
+          sym: Atom::from(self.runtime.import_meta.as_str()),
           optional: Default::default(),
           ctxt: SyntaxContext::empty(),
         })),
@@ -448,9 +535,8 @@ impl Fold for NodeTransform {
   }
 }
 
-#[allow(dead_code)]
-pub fn node_transform() -> impl swc_core::ecma::ast::Pass {
-  fold_pass(NodeTransform)
+pub fn node_transform(runtime: NodeTransformRuntime) -> impl swc_core::ecma::ast::Pass {
+  fold_pass(NodeTransform { runtime })
 }
 
 #[cfg(test)]
@@ -479,7 +565,7 @@ mod tests {
 
   fn test_transform(input: &str, expected_items: usize) {
     let mut module = parse_js(input);
-    let mut transform = NodeTransform;
+    let mut transform = NodeTransform::default();
     module = module.fold_with(&mut transform);
     
     // Basic validation - check that transform produces expected number of items
@@ -535,6 +621,48 @@ export function bar() {}"#;
     test_transform(input, 1);
   }
 
+  #[test]
+  fn test_node_transform_custom_runtime() {
+    let input = r#"import { foo } from 'module';
+export const bar = foo;"#;
+    let mut module = parse_js(input);
+    let mut transform = NodeTransform {
+      runtime: NodeTransformRuntime {
+        import: "__vm_import__".to_string(),
+        exports: "__vm_exports__".to_string(),
+        ..Default::default()
+      },
+    };
+    module = module.fold_with(&mut transform);
+
+    let Some(ModuleItem::Stmt(Stmt::Decl(Decl::Var(import_decl)))) = module.body.first() else {
+      panic!("Expected import to be rewritten into a variable declaration");
+    };
+    let init = import_decl.decls[0].init.as_deref().unwrap();
+    let Expr::Await(AwaitExpr { arg, .. }) = init else {
+      panic!("Expected awaited import helper call");
+    };
+    let Expr::Call(CallExpr { callee: Callee::Expr(callee), .. }) = &**arg else {
+      panic!("Expected import helper call");
+    };
+    assert_eq!(callee.as_ident().unwrap().sym.as_str(), "__vm_import__");
+    assert_eq!(import_decl.decls[0].name.as_ident().unwrap().id.sym.as_str(), "__vm_import_0__");
+  }
+
+  #[test]
+  fn test_node_transform_runtime_config() {
+    let config: NodeTransformConfig =
+      serde_json::from_str(r#"{ "import": "__vm_import__" }"#).unwrap();
+    let NodeTransformConfig::Runtime(runtime) = config else {
+      panic!("Expected runtime helper names");
+    };
+    assert_eq!(runtime.import, "__vm_import__");
+    assert_eq!(runtime.exports, "__ice_exports__");
+
+    // A misspelled helper name would otherwise silently fall back to the default
+    assert!(serde_json::from_str::<NodeTransformConfig>(r#"{ "requireHelper": "__vm_require__" }"#).is_err());
+  }
+
   #[test]
   fn test_node_transform_mixed() {
     let input = r#"import { foo } from 'module1';
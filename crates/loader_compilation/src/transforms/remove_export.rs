@@ -1,5 +1,11 @@
 use std::collections::HashSet as FxHashSet;
 use std::mem::take;
+
+use crate::{
+  options::ExportSelector,
+  transformer::SharedTransformReport,
+  transforms::keep_export::{cjs_export, cjs_prop_name, exported_name, module_bindings, CjsExport},
+};
 use swc_core::{
   common::{
     pass::{Repeat, Repeated},
@@ -17,15 +23,22 @@ struct RemoveExportState {
   refs_from_data_fn: FxHashSet<Id>,
   cur_declaring: FxHashSet<Id>,
   should_run_again: bool,
-  remove_exports: Vec<String>,
+  remove_exports: ExportSelector,
+  report: SharedTransformReport,
+  // Everything the module binds, which shadows the CommonJS `module`/`exports`
+  bindings: FxHashSet<Id>,
 }
 
 impl RemoveExportState {
   fn should_remove_identifier(&mut self, i: &Ident) -> bool {
-    self.remove_exports.contains(&String::from(&*i.sym))
+    self.remove_exports.is_match(&i.sym)
   }
   fn should_remove_default(&mut self) -> bool {
-    self.remove_exports.contains(&String::from("default"))
+    self.remove_exports.is_match("default")
+  }
+
+  fn report_removed_export(&self, name: &str) {
+    self.report.borrow_mut().removed_exports.insert(name.to_string());
   }
 }
 
@@ -113,6 +126,7 @@ impl Fold for RemoveExportImpl {
   }
 
   fn fold_module(&mut self, mut m: Module) -> Module {
+    self.state.bindings = module_bindings(&m);
     {
       let mut v = RemoveExportAnalyzer {
         state: &mut self.state,
@@ -137,6 +151,12 @@ impl Fold for RemoveExportImpl {
       let i = i.fold_with(self);
 
       if !is_for_side_effect && i.specifiers.is_empty() {
+        self
+          .state
+          .report
+          .borrow_mut()
+          .removed_imports
+          .insert(i.src.value.to_string());
         return ModuleItem::Stmt(Stmt::Empty(EmptyStmt { span: DUMMY_SP }));
       }
       return ModuleItem::ModuleDecl(ModuleDecl::Import(i));
@@ -177,6 +197,7 @@ impl Fold for RemoveExportImpl {
 
       match preserve {
         false => {
+          self.state.report_removed_export(&exported_name(s));
           if let ExportSpecifier::Named(ExportNamedSpecifier {
             orig: ModuleExportName::Ident(orig),
             ..
@@ -197,6 +218,7 @@ impl Fold for RemoveExportImpl {
 
   fn fold_default_decl(&mut self, d: DefaultDecl) -> DefaultDecl {
     if self.state.should_remove_default() {
+      self.state.report_removed_export("default");
       return DefaultDecl::Fn(self.create_empty_fn());
     }
     d
@@ -204,6 +226,7 @@ impl Fold for RemoveExportImpl {
 
   fn fold_export_default_expr(&mut self, n: ExportDefaultExpr) -> ExportDefaultExpr {
     if self.state.should_remove_default() {
+      self.state.report_removed_export("default");
       return ExportDefaultExpr {
         span: DUMMY_SP,
         expr: Box::new(Expr::Fn(self.create_empty_fn())),
@@ -358,6 +381,59 @@ impl RemoveExportAnalyzer<'_> {
 
     e.fold_children_with(self)
   }
+
+  fn fold_removed<T: FoldWith<Self>>(&mut self, n: T) -> T {
+    let old_in_data = self.in_data_fn;
+    self.in_data_fn = true;
+    let n = n.fold_with(self);
+    self.in_data_fn = old_in_data;
+    n
+  }
+
+  // `exports.X = ...` and `module.exports = ...` assignments of removed exports
+  fn fold_cjs_export(&mut self, mut s: ExprStmt) -> ModuleItem {
+    let empty = ModuleItem::Stmt(Stmt::Empty(EmptyStmt { span: DUMMY_SP }));
+    let Some(export) = cjs_export(&s.expr, &self.state.bindings) else {
+      return ModuleItem::Stmt(Stmt::Expr(s)).fold_children_with(self);
+    };
+    let Expr::Assign(assign) = &mut *s.expr else {
+      return empty;
+    };
+
+    match export {
+      CjsExport::Named(name) => {
+        if self.state.remove_exports.is_match(&name) {
+          self.state.report_removed_export(&name);
+          self.fold_removed(take(&mut assign.right));
+          return empty;
+        }
+      }
+      CjsExport::Module => match &mut *assign.right {
+        Expr::Object(obj) => {
+          obj.props = take(&mut obj.props)
+            .into_iter()
+            .filter_map(|prop| match cjs_prop_name(&prop) {
+              Some(name) if self.state.remove_exports.is_match(&name) => {
+                self.state.report_removed_export(&name);
+                self.fold_removed(prop);
+                None
+              }
+              _ => Some(prop),
+            })
+            .collect();
+        }
+        _ => {
+          if self.state.should_remove_default() {
+            self.state.report_removed_export("default");
+            self.fold_removed(take(&mut assign.right));
+            return empty;
+          }
+        }
+      },
+    }
+
+    ModuleItem::Stmt(Stmt::Expr(s)).fold_children_with(self)
+  }
 }
 
 impl Fold for RemoveExportAnalyzer<'_> {
@@ -373,7 +449,7 @@ impl Fold for RemoveExportAnalyzer<'_> {
 
   fn fold_export_named_specifier(&mut self, s: ExportNamedSpecifier) -> ExportNamedSpecifier {
     if let ModuleExportName::Ident(id) = &s.orig {
-      if !self.state.remove_exports.contains(&String::from(&*id.sym)) {
+      if !self.state.remove_exports.is_match(&id.sym) {
         self.add_ref(id.to_id());
       }
     }
@@ -387,6 +463,7 @@ impl Fold for RemoveExportAnalyzer<'_> {
     match &s.decl {
       Decl::Fn(f) => {
         if self.state.should_remove_identifier(&f.ident) {
+          self.state.report_removed_export(&f.ident.sym);
           self.in_data_fn = true;
           self.add_ref(f.ident.to_id());
         }
@@ -397,11 +474,8 @@ impl Fold for RemoveExportAnalyzer<'_> {
           return s;
         }
         if let Pat::Ident(id) = &d.decls[0].name {
-          if self
-            .state
-            .remove_exports
-            .contains(&String::from(&*id.id.sym))
-          {
+          if self.state.remove_exports.is_match(&id.id.sym) {
+            self.state.report_removed_export(&id.id.sym);
             self.in_data_fn = true;
             self.add_ref(id.to_id());
           }
@@ -477,6 +551,9 @@ impl Fold for RemoveExportAnalyzer<'_> {
 
         return ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(e));
       }
+      ModuleItem::Stmt(Stmt::Expr(e)) => {
+        return self.fold_cjs_export(e);
+      }
       _ => {}
     };
 
@@ -542,10 +619,14 @@ impl Fold for RemoveExportAnalyzer<'_> {
   }
 }
 
-pub fn remove_export(exports: Vec<String>) -> impl swc_core::ecma::ast::Pass {
+pub fn remove_export(
+  exports: ExportSelector,
+  report: SharedTransformReport,
+) -> impl swc_core::ecma::ast::Pass {
   fold_pass(Repeat::new(RemoveExportImpl {
     state: RemoveExportState {
       remove_exports: exports,
+      report,
       ..Default::default()
     },
     in_lhs_of_var: false,
@@ -558,6 +639,7 @@ mod tests {
   use swc_core::{
     common::{FileName, SourceMap},
     ecma::{
+      codegen::{text_writer::JsWriter, Emitter},
       parser::{lexer::Lexer, Parser, StringInput, Syntax},
       visit::FoldWith,
     },
@@ -580,7 +662,7 @@ mod tests {
     let mut module = parse_js(input);
     let mut transform = Repeat::new(RemoveExportImpl {
       state: RemoveExportState {
-        remove_exports,
+        remove_exports: remove_exports.into(),
         ..Default::default()
       },
       in_lhs_of_var: false,
@@ -845,4 +927,108 @@ export const getConfig = () => {
     
     test_transform(input, expected, vec!["getData".to_string()]);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_remove_export_patterns() {
+    let mut module = parse_js(
+      r#"import db from './db';
+export const getServerData = () => db.query();
+export const getStaticData = () => 2;
+export const unstable_settings = {};
+export function getConfig() { return {}; }"#,
+    );
+    module = module.fold_with(&mut Repeat::new(RemoveExportImpl {
+      state: RemoveExportState {
+        remove_exports: serde_json::from_str(r#"["get*Data", "/^unstable_/"]"#).unwrap(),
+        ..Default::default()
+      },
+      in_lhs_of_var: false,
+    }));
+
+    // Removed data exports become empty functions, and their imports are pruned
+    let imports = module
+      .body
+      .iter()
+      .filter(|item| matches!(item, ModuleItem::ModuleDecl(ModuleDecl::Import(_))))
+      .count();
+    assert_eq!(imports, 0);
+    let config_kept = module.body.iter().any(|item| {
+      matches!(item, ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl { decl: Decl::Fn(f), .. })) if f.ident.sym == "getConfig" && !f.function.body.as_ref().unwrap().stmts.is_empty())
+    });
+    assert!(config_kept);
+  }
+
+  fn emit_js(module: &Module) -> String {
+    let mut buf = vec![];
+    {
+      let writer = JsWriter::new(SourceMap::default().into(), "\n", &mut buf, None);
+      let mut emitter = Emitter {
+        cfg: Default::default(),
+        comments: None,
+        cm: SourceMap::default().into(),
+        wr: writer,
+      };
+      emitter.emit_module(module).unwrap();
+    }
+    String::from_utf8(buf).unwrap()
+  }
+
+  fn remove_cjs(input: &str, remove_exports: Vec<String>) -> String {
+    let module = parse_js(input).fold_with(&mut Repeat::new(RemoveExportImpl {
+      state: RemoveExportState {
+        remove_exports: remove_exports.into(),
+        ..Default::default()
+      },
+      in_lhs_of_var: false,
+    }));
+    emit_js(&module)
+  }
+
+  #[test]
+  fn test_remove_export_commonjs_assignments() {
+    let output = remove_cjs(
+      r#"const db = require('./db');
+const helper = require('./helper');
+exports.getData = () => db.query();
+module.exports.getConfig = function () { return helper(); };
+exports['getTitle'] = 'title';
+console.log('side effect');"#,
+      vec!["getData".to_string(), "getTitle".to_string()],
+    );
+
+    assert!(output.contains("module.exports.getConfig"), "{output}");
+    assert!(output.contains("require('./helper')"), "{output}");
+    assert!(output.contains("console.log"), "{output}");
+    assert!(!output.contains("getData"), "{output}");
+    assert!(!output.contains("getTitle"), "{output}");
+    assert!(!output.contains("./db"), "{output}");
+  }
+
+  #[test]
+  fn test_remove_export_commonjs_object() {
+    let output = remove_cjs(
+      r#"const db = require('./db');
+function getConfig() { return {}; }
+module.exports = {
+  getConfig,
+  getData: () => db.query(),
+  'getTitle': 'title',
+};"#,
+      vec!["getData".to_string(), "getTitle".to_string()],
+    );
+
+    assert!(output.contains("module.exports = {"), "{output}");
+    assert!(output.contains("getConfig"), "{output}");
+    assert!(!output.contains("getData"), "{output}");
+    assert!(!output.contains("getTitle"), "{output}");
+    assert!(!output.contains("./db"), "{output}");
+
+    let output = remove_cjs(
+      r#"const db = require('./db');
+module.exports = () => db.query();"#,
+      vec!["default".to_string()],
+    );
+    assert!(!output.contains("module.exports"), "{output}");
+    assert!(!output.contains("./db"), "{output}");
+  }
+}
@@ -1,19 +1,71 @@
 use std::{collections::HashMap, path::Path};
 
 use rspack_core::{
-  CompilationAsset, Plugin,
+  CompilationAsset, CompilationAssets, Plugin,
   PublicPath, Compilation,
   CompilationProcessAssets,
   ApplyContext,
 };
+use base64::{engine::general_purpose::STANDARD, Engine};
 use rspack_sources::{RawSource, SourceExt};
 use rspack_error::Result;
 use rspack_hook::{plugin, plugin_hook};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384, Sha512};
 
 #[plugin]
 #[derive(Debug)]
-pub struct ManifestPlugin;
+pub struct ManifestPlugin {
+  options: ManifestPluginOptions,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ManifestPluginOptions {
+  /// Name of the emitted manifest asset.
+  pub filename: String,
+  /// Prefix stripped from async chunk names to get the page name.
+  pub page_chunk_prefix: String,
+  /// Data loader source, relative to the compiler context.
+  pub data_loader_source: String,
+  /// Asset name recorded as `dataLoader` when the data loader source exists.
+  pub data_loader_filename: String,
+  /// Hash function used for the SRI `integrity` of every emitted file. When set, the
+  /// manifest gets a `files` section with the `size` and `integrity` of each asset.
+  pub integrity: Option<IntegrityHashFunction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegrityHashFunction {
+  Sha256,
+  Sha384,
+  Sha512,
+}
+
+impl IntegrityHashFunction {
+  /// Returns the SRI integrity string, e.g. `sha384-<base64 digest>`.
+  pub fn integrity(&self, content: &[u8]) -> String {
+    let (prefix, digest) = match self {
+      IntegrityHashFunction::Sha256 => ("sha256", Sha256::digest(content).to_vec()),
+      IntegrityHashFunction::Sha384 => ("sha384", Sha384::digest(content).to_vec()),
+      IntegrityHashFunction::Sha512 => ("sha512", Sha512::digest(content).to_vec()),
+    };
+    format!("{prefix}-{}", STANDARD.encode(digest))
+  }
+}
+
+impl Default for ManifestPluginOptions {
+  fn default() -> Self {
+    Self {
+      filename: "assets-manifest.json".to_string(),
+      page_chunk_prefix: "p_".to_string(),
+      data_loader_source: ".ice/data-loader.ts".to_string(),
+      data_loader_filename: "js/data-loader.js".to_string(),
+      integrity: None,
+    }
+  }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -23,19 +75,28 @@ pub struct AssetsManifest {
   pub assets: HashMap<String, String>,
   pub public_path: String,
   pub data_loader: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub files: Option<HashMap<String, AssetFile>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetFile {
+  pub size: usize,
+  pub integrity: String,
 }
 
 const AUTO_PUBLIC_PATH_PLACEHOLDER: &str = "__RSPACK_PLUGIN_CSS_AUTO_PUBLIC_PATH__";
 
 impl Default for ManifestPlugin {
   fn default() -> Self {
-    Self::new()
+    Self::new(Default::default())
   }
 }
 
 impl ManifestPlugin {
-  pub fn new() -> Self {
-    Self::new_inner()
+  pub fn new(options: ManifestPluginOptions) -> Self {
+    Self::new_inner(options)
   }
 }
 
@@ -51,6 +112,7 @@ async fn process_assets(&self, compilation: &mut Compilation) -> Result<()> {
     assets: HashMap::new(),
     public_path: public_path.unwrap_or_default().to_string(),
     data_loader: None,
+    files: None,
   };
   let entry_points = &compilation.entrypoints;
   let assets = &compilation.assets();
@@ -64,7 +126,7 @@ async fn process_assets(&self, compilation: &mut Compilation) -> Result<()> {
         .insert(name.to_string(), version.to_string());
     }
   });
-  
+
   entry_points.iter().for_each(|(name, _entry)| {
     let mut files: Vec<String> = Vec::new();
     let entrypoint = compilation.entrypoint_by_name(name);
@@ -87,21 +149,23 @@ async fn process_assets(&self, compilation: &mut Compilation) -> Result<()> {
     assets_manifest.entries.insert(name.to_string(), files);
   });
   
-  // Check .ice/data-loader.ts is exists
+  // Check the data loader source (.ice/data-loader.ts by default) is exists
   let data_loader_file =
-    Path::new(&compilation.options.context.as_str()).join(".ice/data-loader.ts");
+    Path::new(&compilation.options.context.as_str()).join(&self.options.data_loader_source);
   if data_loader_file.exists() {
-    assets_manifest.data_loader = Some("js/data-loader.js".to_string());
+    assets_manifest.data_loader = Some(self.options.data_loader_filename.clone());
   }
 
-  let page_chunk_name_regex = regex::Regex::new(r"^p_").unwrap();
   compilation.chunk_by_ukey.values().for_each(|c| {
     if let Some(name) = c.name() {
       if !c.has_entry_module(&compilation.chunk_graph)
         && !c.can_be_initial(&compilation.chunk_group_by_ukey)
       {
         assets_manifest.pages.insert(
-          page_chunk_name_regex.replace(name, "").to_string(),
+          name
+            .strip_prefix(self.options.page_chunk_prefix.as_str())
+            .unwrap_or(name)
+            .to_string(),
           Vec::from_iter(
             c.files()
               .iter()
@@ -116,12 +180,59 @@ async fn process_assets(&self, compilation: &mut Compilation) -> Result<()> {
   
   let json_string = serde_json::to_string(&assets_manifest).unwrap();
   compilation.emit_asset(
-    "assets-manifest.json".to_string(),
+    self.options.filename.clone(),
     CompilationAsset::from(RawSource::from(json_string).boxed()),
   );
   Ok(())
 }
 
+// Minification and real content hashing rewrite assets after ADDITIONS, so the `files`
+// section is only filled in once every asset has its final name and source
+#[plugin_hook(CompilationProcessAssets for ManifestPlugin, stage = Compilation::PROCESS_ASSETS_STAGE_REPORT)]
+async fn process_assets_report(&self, compilation: &mut Compilation) -> Result<()> {
+  if let Some(hash_function) = self.options.integrity {
+    write_manifest_files(&self.options.filename, hash_function, compilation.assets_mut())?;
+  }
+  Ok(())
+}
+
+/// Adds the `size` and `integrity` of every emitted file to the manifest asset `filename`.
+fn write_manifest_files(
+  filename: &str,
+  hash_function: IntegrityHashFunction,
+  assets: &mut CompilationAssets,
+) -> Result<()> {
+  let files = assets
+    .iter()
+    .filter(|(file, asset)| {
+      file.as_str() != filename && !asset.info.hot_module_replacement.unwrap_or(false)
+    })
+    .filter_map(|(file, asset)| {
+      let content = asset.get_source()?.buffer();
+      Some((
+        file.to_string(),
+        AssetFile {
+          size: content.len(),
+          integrity: hash_function.integrity(&content),
+        },
+      ))
+    })
+    .collect();
+
+  let Some(manifest_asset) = assets.get_mut(filename) else {
+    return Ok(());
+  };
+  let Some(source) = manifest_asset.get_source() else {
+    return Ok(());
+  };
+  let mut assets_manifest: AssetsManifest = serde_json::from_slice(&source.buffer())
+    .map_err(|e| rspack_error::error!("Failed to read {filename}: {e}"))?;
+  assets_manifest.files = Some(files);
+  let json_string = serde_json::to_string(&assets_manifest).unwrap();
+  manifest_asset.set_source(Some(RawSource::from(json_string).boxed()));
+  Ok(())
+}
+
 impl Plugin for ManifestPlugin {
   fn name(&self) -> &'static str {
     "ManifestPlugin"
@@ -135,6 +246,110 @@ impl Plugin for ManifestPlugin {
       .compilation_hooks
       .process_assets
       .tap(process_assets::new(self));
+    ctx
+      .compilation_hooks
+      .process_assets
+      .tap(process_assets_report::new(self));
     Ok(())
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_options_default() {
+    let options: ManifestPluginOptions = serde_json::from_str("{}").unwrap();
+    assert_eq!(options.filename, "assets-manifest.json");
+    assert_eq!(options.page_chunk_prefix, "p_");
+    assert_eq!(options.data_loader_source, ".ice/data-loader.ts");
+    assert_eq!(options.data_loader_filename, "js/data-loader.js");
+    assert_eq!(options.integrity, None);
+  }
+
+  #[test]
+  fn test_options_integrity() {
+    let options: ManifestPluginOptions =
+      serde_json::from_str(r#"{ "integrity": "sha384" }"#).unwrap();
+    assert_eq!(options.integrity, Some(IntegrityHashFunction::Sha384));
+    assert!(serde_json::from_str::<ManifestPluginOptions>(r#"{ "integrity": "md5" }"#).is_err());
+  }
+
+  #[test]
+  fn test_integrity() {
+    // Digests of "alert('Hello, world.');" from the SRI specification examples
+    let content = b"alert('Hello, world.');";
+    assert_eq!(
+      IntegrityHashFunction::Sha256.integrity(content),
+      "sha256-qznLcsROx4GACP2dm0UCKCzCG+HiZ1guq6ZZDob/Tng="
+    );
+    assert_eq!(
+      IntegrityHashFunction::Sha384.integrity(content),
+      "sha384-H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO"
+    );
+    assert!(IntegrityHashFunction::Sha512
+      .integrity(content)
+      .starts_with("sha512-"));
+  }
+
+  #[test]
+  fn test_manifest_files_skipped_without_integrity() {
+    let manifest = AssetsManifest {
+      pages: HashMap::new(),
+      entries: HashMap::new(),
+      assets: HashMap::new(),
+      public_path: "/".to_string(),
+      data_loader: None,
+      files: None,
+    };
+    let json = serde_json::to_string(&manifest).unwrap();
+    assert!(!json.contains("files"), "{json}");
+  }
+
+  fn js_asset(source: &str) -> CompilationAsset {
+    CompilationAsset::from(RawSource::from(source.to_string()).boxed())
+  }
+
+  #[test]
+  fn test_write_manifest_files() {
+    let manifest = AssetsManifest {
+      pages: HashMap::new(),
+      entries: HashMap::from([("main".to_string(), vec!["js/main.1a2b.js".to_string()])]),
+      assets: HashMap::new(),
+      public_path: "/".to_string(),
+      data_loader: None,
+      files: None,
+    };
+    let mut assets = CompilationAssets::default();
+    assets.insert(
+      "assets-manifest.json".to_string(),
+      CompilationAsset::from(RawSource::from(serde_json::to_string(&manifest).unwrap()).boxed()),
+    );
+    assets.insert("js/main.1a2b.js".to_string(), js_asset("alert('Hello, world.');"));
+    let mut hot_update = js_asset("self.hotUpdate();");
+    hot_update.info.hot_module_replacement = Some(true);
+    assets.insert("main.hot-update.js".to_string(), hot_update);
+
+    write_manifest_files("assets-manifest.json", IntegrityHashFunction::Sha256, &mut assets).unwrap();
+
+    let source = assets["assets-manifest.json"].get_source().unwrap().buffer();
+    let manifest: AssetsManifest = serde_json::from_slice(&source).unwrap();
+    let files = manifest.files.unwrap();
+    assert_eq!(files.len(), 1, "{files:?}");
+    let main = &files["js/main.1a2b.js"];
+    assert_eq!(main.size, 23);
+    assert_eq!(main.integrity, "sha256-qznLcsROx4GACP2dm0UCKCzCG+HiZ1guq6ZZDob/Tng=");
+    assert_eq!(manifest.entries["main"], vec!["js/main.1a2b.js".to_string()]);
+  }
+
+  #[test]
+  fn test_options_partial() {
+    let options: ManifestPluginOptions =
+      serde_json::from_str(r#"{ "filename": "manifest.json", "pageChunkPrefix": "page_" }"#)
+        .unwrap();
+    assert_eq!(options.filename, "manifest.json");
+    assert_eq!(options.page_chunk_prefix, "page_");
+    assert_eq!(options.data_loader_source, ".ice/data-loader.ts");
+  }
+}